@@ -19,15 +19,27 @@ pub struct CWorkerParameters {
   pub parameters: HashMap<String, Value>,
 }
 
-fn get_instance_type_from_parameter_type(parameter_type: &ParameterType) -> InstanceType {
-  match parameter_type {
+fn get_instance_type_from_parameter_type(parameter_type: &ParameterType) -> Vec<InstanceType> {
+  let instance_type = match parameter_type {
     ParameterType::String => InstanceType::String,
+    ParameterType::ArrayOfBooleans => InstanceType::Array,
+    ParameterType::ArrayOfFloats => InstanceType::Array,
+    ParameterType::ArrayOfIntegers => InstanceType::Array,
+    ParameterType::ArrayOfMediaSegments => InstanceType::Array,
     ParameterType::ArrayOfStrings => InstanceType::Array,
     ParameterType::Boolean => InstanceType::Boolean,
     ParameterType::Credential => InstanceType::String,
+    ParameterType::DateTime => InstanceType::String,
+    // a number of seconds, or a string like `500ms`
+    ParameterType::Duration => return vec![InstanceType::Number, InstanceType::String],
+    ParameterType::Float => InstanceType::Number,
     ParameterType::Integer => InstanceType::Integer,
+    ParameterType::Json => InstanceType::Object,
+    ParameterType::Object => InstanceType::Object,
     ParameterType::Requirements => InstanceType::Array,
-  }
+    ParameterType::Url => InstanceType::String,
+  };
+  vec![instance_type]
 }
 
 impl JsonSchema for CWorkerParameters {
@@ -45,11 +57,9 @@ impl JsonSchema for CWorkerParameters {
         instance_type: Some(if parameter.required {
           get_instance_type_from_parameter_type(parameter_type).into()
         } else {
-          vec![
-            get_instance_type_from_parameter_type(parameter_type),
-            InstanceType::Null,
-          ]
-          .into()
+          let mut instance_types = get_instance_type_from_parameter_type(parameter_type);
+          instance_types.push(InstanceType::Null);
+          instance_types.into()
         }),
         ..Default::default()
       };
@@ -108,27 +118,43 @@ pub unsafe fn get_parameter_from_worker_parameter(worker_parameter: &WorkerParam
 #[test]
 pub fn test_get_instance_type_from_parameter() {
   assert_eq!(
-    InstanceType::String,
+    vec![InstanceType::String],
     get_instance_type_from_parameter_type(&ParameterType::String)
   );
   assert_eq!(
-    InstanceType::Array,
+    vec![InstanceType::Array],
     get_instance_type_from_parameter_type(&ParameterType::ArrayOfStrings)
   );
   assert_eq!(
-    InstanceType::Boolean,
+    vec![InstanceType::Boolean],
     get_instance_type_from_parameter_type(&ParameterType::Boolean)
   );
   assert_eq!(
-    InstanceType::String,
+    vec![InstanceType::String],
     get_instance_type_from_parameter_type(&ParameterType::Credential)
   );
   assert_eq!(
-    InstanceType::Integer,
+    vec![InstanceType::Integer],
     get_instance_type_from_parameter_type(&ParameterType::Integer)
   );
   assert_eq!(
-    InstanceType::Array,
+    vec![InstanceType::Array],
     get_instance_type_from_parameter_type(&ParameterType::Requirements)
   );
+  assert_eq!(
+    vec![InstanceType::Number],
+    get_instance_type_from_parameter_type(&ParameterType::Float)
+  );
+  assert_eq!(
+    vec![InstanceType::Array],
+    get_instance_type_from_parameter_type(&ParameterType::ArrayOfIntegers)
+  );
+  assert_eq!(
+    vec![InstanceType::String],
+    get_instance_type_from_parameter_type(&ParameterType::DateTime)
+  );
+  assert_eq!(
+    vec![InstanceType::Number, InstanceType::String],
+    get_instance_type_from_parameter_type(&ParameterType::Duration)
+  );
 }
@@ -2,7 +2,11 @@ mod bind_description;
 mod exchange_description;
 mod queue_description;
 
-use crate::worker::WorkerConfiguration;
+use crate::{
+  config::{get_job_retry_delays, get_worker_concurrency},
+  worker::WorkerConfiguration,
+  MessageError, Result,
+};
 use bind_description::BindDescription;
 use exchange_description::ExchangeDescription;
 use lapin::{
@@ -16,6 +20,7 @@ static EXCHANGE_NAME_SUBMIT: &str = "job_submit";
 static EXCHANGE_NAME_RESPONSE: &str = "job_response";
 static EXCHANGE_NAME_DELAYED: &str = "job_delayed";
 static EXCHANGE_NAME_DIRECT_MESSAGING: &str = "direct_messaging";
+static EXCHANGE_NAME_RETRY_DELAYED_PREFIX: &str = "job_delayed_";
 static EXCHANGE_NAME_RESPONSE_DELAYED: &str = "job_response_delayed";
 
 static QUEUE_NAME_WORKER_DISCOVERY: &str = "worker_discovery";
@@ -23,9 +28,11 @@ static QUEUE_NAME_WORKER_DISCOVERY: &str = "worker_discovery";
 pub fn declare_consumer_channel(
   conn: &Connection,
   worker_configuration: &WorkerConfiguration,
-) -> Channel {
-  let channel = conn.create_channel().wait().unwrap();
-  let prefetch_count = 1;
+) -> Result<Channel> {
+  let channel = conn.create_channel().wait().map_err(|error| {
+    MessageError::RuntimeError(format!("Unable to create channel: {:?}", error))
+  })?;
+  let prefetch_count = get_worker_concurrency();
 
   info!("Initialise Exchanges and Queues");
   set_qos(&channel, prefetch_count);
@@ -73,6 +80,10 @@ pub fn declare_consumer_channel(
   };
   delayed_bind.declare(&channel);
 
+  for delay in get_job_retry_delays() {
+    declare_retry_delayed_queue(&channel, delay);
+  }
+
   let direct_messaging_exchange = ExchangeDescription {
     name: EXCHANGE_NAME_DIRECT_MESSAGING.to_string(),
     kind: ExchangeKind::Headers,
@@ -176,7 +187,43 @@ pub fn declare_consumer_channel(
   delayed_bind.declare(&channel);
 
   info!("Exchanges and Queues are configured.");
-  channel
+  Ok(channel)
+}
+
+/// Name of the exchange delaying the retries of a job
+pub fn get_retry_delayed_exchange_name(delay: i32) -> String {
+  format!("{}{}", EXCHANGE_NAME_RETRY_DELAYED_PREFIX, delay)
+}
+
+/// Declare a queue holding the messages during the delay, before sending them back to their job queue
+fn declare_retry_delayed_queue(channel: &Channel, delay: i32) {
+  let name = get_retry_delayed_exchange_name(delay);
+
+  let exchange = ExchangeDescription {
+    name: name.clone(),
+    kind: ExchangeKind::Fanout,
+    alternate_exchange: None,
+  };
+  exchange.declare(channel);
+
+  let queue = QueueDescription {
+    name: name.clone(),
+    durable: true,
+    auto_delete: false,
+    dead_letter_exchange: Some("".to_string()),
+    dead_letter_routing_key: None,
+    max_priority: None,
+    message_ttl: Some(delay),
+  };
+  queue.declare(channel);
+
+  let bind = BindDescription {
+    exchange: name.clone(),
+    queue: name,
+    routing_key: "*".to_string(),
+    headers: HashMap::new(),
+  };
+  bind.declare(channel);
 }
 
 fn set_qos(channel: &Channel, prefetch_count: u16) {
@@ -1,5 +1,6 @@
 use amq_protocol_types::AMQPValue;
 use lapin::{options::QueueDeclareOptions, types::FieldTable, Channel};
+use std::convert::TryFrom;
 
 pub struct QueueDescription {
   pub name: String,
@@ -8,7 +9,7 @@ pub struct QueueDescription {
   pub dead_letter_exchange: Option<String>,
   pub dead_letter_routing_key: Option<String>,
   pub max_priority: Option<i16>,
-  pub message_ttl: Option<i16>,
+  pub message_ttl: Option<i32>,
 }
 
 impl QueueDescription {
@@ -49,8 +50,12 @@ impl QueueDescription {
       queue_fields.insert("x-max-priority".into(), AMQPValue::ShortInt(*max_priority));
     }
 
-    if let Some(message_ttl) = &self.message_ttl {
-      queue_fields.insert("x-message-ttl".into(), AMQPValue::ShortInt(*message_ttl));
+    if let Some(message_ttl) = self.message_ttl {
+      // the existing queues are declared with a short TTL, the server rejects another type
+      let message_ttl = i16::try_from(message_ttl)
+        .map(AMQPValue::ShortInt)
+        .unwrap_or(AMQPValue::LongInt(message_ttl));
+      queue_fields.insert("x-message-ttl".into(), message_ttl);
     }
     queue_fields
   }
@@ -96,7 +101,26 @@ pub fn test_queue_description() {
     tree_map.get("x-max-priority").unwrap()
   );
   assert_eq!(
-    &AMQPValue::ShortInt(message_ttl.unwrap().into()),
+    &AMQPValue::ShortInt(message_ttl.unwrap() as i16),
     tree_map.get("x-message-ttl").unwrap()
   );
 }
+
+#[test]
+pub fn test_queue_description_long_message_ttl() {
+  let queue_description = QueueDescription {
+    name: "queue_name".to_string(),
+    durable: true,
+    auto_delete: false,
+    dead_letter_exchange: None,
+    dead_letter_routing_key: None,
+    max_priority: None,
+    message_ttl: Some(60000),
+  };
+
+  let field_table = queue_description.get_field_table();
+  assert_eq!(
+    &AMQPValue::LongInt(60000),
+    field_table.inner().get("x-message-ttl").unwrap()
+  );
+}
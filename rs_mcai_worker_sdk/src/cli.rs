@@ -0,0 +1,260 @@
+//! Command line interface shared by the workers
+//!
+//! Flags override the corresponding environment variables.
+
+use std::{collections::HashMap, env};
+use structopt::StructOpt;
+
+/// Environment variables set from the flags of a command
+type Environment = HashMap<&'static str, String>;
+
+#[derive(Debug, StructOpt)]
+pub struct Cli {
+  #[structopt(subcommand)]
+  command: Option<Command>,
+}
+
+impl Cli {
+  /// Returns the command to execute, consuming jobs by default
+  pub fn get_command(self) -> Command {
+    self
+      .command
+      .unwrap_or_else(|| Command::Consume(ConsumeOptions::default()))
+  }
+}
+
+#[derive(Debug, PartialEq, StructOpt)]
+pub enum Command {
+  /// Print the worker configuration
+  Describe(DescribeOptions),
+  /// Print the JSON schema of the job parameters
+  Schema,
+  /// Process local orders, without RabbitMQ
+  Run(RunOptions),
+  /// Parse an order and check its parameters, without processing it
+  Validate {
+    /// Path to the order
+    order: String,
+  },
+  /// Consume the jobs of the AMQP queue (default)
+  Consume(ConsumeOptions),
+  /// Process the job message read on the standard input, in an isolated child process
+  #[structopt(setting = structopt::clap::AppSettings::Hidden)]
+  ProcessJob {
+    /// Path to the workspace of the job
+    #[structopt(long)]
+    workspace: Option<String>,
+  },
+}
+
+impl Command {
+  /// Override the environment variables with the flags of the command
+  pub fn apply_environment(&self) {
+    for (key, value) in self.get_environment() {
+      env::set_var(key, value);
+    }
+  }
+
+  /// Environment variables overridden by the flags of the command
+  pub fn get_environment(&self) -> Environment {
+    let mut environment = Environment::new();
+    match self {
+      Command::Describe(options) => options.fill_environment(&mut environment),
+      Command::Run(options) => options.fill_environment(&mut environment),
+      Command::Consume(options) => options.fill_environment(&mut environment),
+      Command::Schema | Command::Validate { .. } | Command::ProcessJob { .. } => {}
+    }
+    environment
+  }
+}
+
+#[derive(Debug, Default, PartialEq, StructOpt)]
+pub struct DescribeOptions {
+  /// AMQP queue name used to receive job orders
+  #[structopt(long)]
+  pub amqp_queue: Option<String>,
+}
+
+impl DescribeOptions {
+  fn fill_environment(&self, environment: &mut Environment) {
+    set_env_value(environment, "AMQP_QUEUE", &self.amqp_queue);
+  }
+}
+
+#[derive(Debug, Default, PartialEq, StructOpt)]
+pub struct RunOptions {
+  /// Paths to the orders, directories or glob patterns
+  #[structopt(required = true)]
+  pub orders: Vec<String>,
+  /// Directory where the results and progressions are written, instead of the standard output
+  #[structopt(long, short)]
+  pub output: Option<String>,
+}
+
+impl RunOptions {
+  fn fill_environment(&self, environment: &mut Environment) {
+    set_env_value(environment, "SOURCE_ORDERS_OUTPUT", &self.output);
+  }
+}
+
+#[derive(Debug, Default, PartialEq, StructOpt)]
+pub struct ConsumeOptions {
+  /// IP or host of AMQP server
+  #[structopt(long)]
+  pub amqp_hostname: Option<String>,
+  /// AMQP server port
+  #[structopt(long)]
+  pub amqp_port: Option<u16>,
+  /// Enable secure connection using AMQPS
+  #[structopt(long)]
+  pub amqp_tls: Option<bool>,
+  /// Username used to connect to AMQP server
+  #[structopt(long)]
+  pub amqp_username: Option<String>,
+  /// Password used to connect to AMQP server
+  #[structopt(long)]
+  pub amqp_password: Option<String>,
+  /// AMQP virtual host
+  #[structopt(long)]
+  pub amqp_vhost: Option<String>,
+  /// AMQP queue name used to receive job orders
+  #[structopt(long)]
+  pub amqp_queue: Option<String>,
+  /// Number of jobs processed in parallel
+  #[structopt(long)]
+  pub concurrency: Option<u16>,
+  /// Delay in seconds to let running jobs finish on termination
+  #[structopt(long)]
+  pub shutdown_grace_period: Option<u64>,
+  /// Number of retries of a rejected job
+  #[structopt(long)]
+  pub max_retries: Option<i64>,
+  /// Port of the HTTP server exposing the Prometheus metrics and the health probes
+  #[structopt(long)]
+  pub http_port: Option<u16>,
+  /// Process each job in a child process, to survive its crashes. The worker is initialized in
+  /// each child process, which delays every job (see `MessageEvent::init_job_process`)
+  #[structopt(long)]
+  pub isolation: bool,
+}
+
+impl ConsumeOptions {
+  fn fill_environment(&self, environment: &mut Environment) {
+    set_env_value(environment, "AMQP_HOSTNAME", &self.amqp_hostname);
+    set_env_value(environment, "AMQP_PORT", &self.amqp_port);
+    set_env_value(environment, "AMQP_TLS", &self.amqp_tls);
+    set_env_value(environment, "AMQP_USERNAME", &self.amqp_username);
+    set_env_value(environment, "AMQP_PASSWORD", &self.amqp_password);
+    set_env_value(environment, "AMQP_VHOST", &self.amqp_vhost);
+    set_env_value(environment, "AMQP_QUEUE", &self.amqp_queue);
+    set_env_value(environment, "WORKER_CONCURRENCY", &self.concurrency);
+    set_env_value(
+      environment,
+      "SHUTDOWN_GRACE_PERIOD",
+      &self.shutdown_grace_period,
+    );
+    set_env_value(environment, "JOB_MAX_RETRIES", &self.max_retries);
+    set_env_value(environment, "HTTP_PORT", &self.http_port);
+    if self.isolation {
+      environment.insert("JOB_ISOLATION", "true".to_string());
+    }
+  }
+}
+
+fn set_env_value<T: ToString>(environment: &mut Environment, key: &'static str, value: &Option<T>) {
+  if let Some(value) = value {
+    environment.insert(key, value.to_string());
+  }
+}
+
+#[test]
+fn command_line_parsing() {
+  let command = Cli::from_iter_safe(vec!["worker"]).unwrap().get_command();
+  assert_eq!(command, Command::Consume(ConsumeOptions::default()));
+
+  let command = Cli::from_iter_safe(vec!["worker", "describe"])
+    .unwrap()
+    .get_command();
+  assert_eq!(command, Command::Describe(DescribeOptions::default()));
+
+  let command = Cli::from_iter_safe(vec!["worker", "schema"])
+    .unwrap()
+    .get_command();
+  assert_eq!(command, Command::Schema);
+
+  let command = Cli::from_iter_safe(vec!["worker", "validate", "order.json"])
+    .unwrap()
+    .get_command();
+  assert_eq!(
+    command,
+    Command::Validate {
+      order: "order.json".to_string()
+    }
+  );
+
+  let command = Cli::from_iter_safe(vec!["worker", "run", "a.json", "orders/", "-o", "results"])
+    .unwrap()
+    .get_command();
+  assert_eq!(
+    command,
+    Command::Run(RunOptions {
+      orders: vec!["a.json".to_string(), "orders/".to_string()],
+      output: Some("results".to_string()),
+    })
+  );
+
+  let command = Cli::from_iter_safe(vec!["worker", "process-job", "--workspace", "/tmp/job_1"])
+    .unwrap()
+    .get_command();
+  assert_eq!(
+    command,
+    Command::ProcessJob {
+      workspace: Some("/tmp/job_1".to_string())
+    }
+  );
+
+  assert!(Cli::from_iter_safe(vec!["worker", "run"]).is_err());
+  assert!(Cli::from_iter_safe(vec!["worker", "consume", "--amqp-port", "BAD_VALUE"]).is_err());
+}
+
+#[test]
+fn command_line_environment() {
+  let command = Cli::from_iter_safe(vec![
+    "worker",
+    "consume",
+    "--amqp-hostname",
+    "rabbitmq",
+    "--concurrency",
+    "4",
+    "--isolation",
+    "--http-port",
+    "9090",
+  ])
+  .unwrap()
+  .get_command();
+
+  // the process environment is shared with the tests reading the configuration
+  let environment = command.get_environment();
+  assert_eq!(
+    environment.get("AMQP_HOSTNAME"),
+    Some(&"rabbitmq".to_string())
+  );
+  assert_eq!(
+    environment.get("WORKER_CONCURRENCY"),
+    Some(&"4".to_string())
+  );
+  assert_eq!(environment.get("JOB_ISOLATION"), Some(&"true".to_string()));
+  assert_eq!(environment.get("HTTP_PORT"), Some(&"9090".to_string()));
+  assert!(environment.get("AMQP_USERNAME").is_none());
+  assert_eq!(environment.len(), 4);
+
+  let command = Cli::from_iter_safe(vec!["worker", "run", "a.json", "-o", "results"])
+    .unwrap()
+    .get_command();
+  let environment = command.get_environment();
+  assert_eq!(
+    environment.get("SOURCE_ORDERS_OUTPUT"),
+    Some(&"results".to_string())
+  );
+  assert_eq!(environment.len(), 1);
+}
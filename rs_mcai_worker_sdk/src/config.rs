@@ -43,6 +43,110 @@ pub fn get_amqp_queue() -> String {
   get_env_value!("AMQP_QUEUE", "job_undefined")
 }
 
+#[cfg(not(feature = "media"))]
+pub fn get_worker_concurrency() -> u16 {
+  let value = get_env_value!("WORKER_CONCURRENCY", "1");
+  match value.parse::<u16>() {
+    Ok(value) if value > 0 => value,
+    _ => 1,
+  }
+}
+
+/// Media workers keep a job context between their callbacks
+#[cfg(feature = "media")]
+pub fn get_worker_concurrency() -> u16 {
+  1
+}
+
+pub fn get_job_max_retries() -> i64 {
+  let value = get_env_value!("JOB_MAX_RETRIES", "10");
+  value.parse::<i64>().unwrap_or(10)
+}
+
+pub fn get_job_progression_interval() -> u64 {
+  let value = get_env_value!("JOB_PROGRESSION_INTERVAL", "500");
+  value.parse::<u64>().unwrap_or(500)
+}
+
+/// Delays in milliseconds between the retries of a job, the last one is used for the next retries
+pub fn get_job_retry_delays() -> Vec<i32> {
+  let value = get_env_value!("JOB_RETRY_DELAYS", "5000,30000,300000,1800000");
+  let delays: Vec<i32> = value
+    .split(',')
+    .filter_map(|delay| delay.trim().parse::<i32>().ok())
+    .filter(|delay| *delay > 0)
+    .collect();
+
+  if delays.is_empty() {
+    vec![5000, 30000, 300000, 1800000]
+  } else {
+    delays
+  }
+}
+
+pub fn get_job_isolation() -> bool {
+  let value = get_env_value!("JOB_ISOLATION", "false");
+  matches!(value.as_str(), "true" | "1" | "True" | "TRUE")
+}
+
+/// Whether the logs are written as JSON lines, instead of text lines
+pub fn get_log_json() -> bool {
+  let value = get_env_value!("LOG_FORMAT", "text");
+  value.to_lowercase() == "json"
+}
+
+/// Port of the embedded HTTP server, it is disabled if it is not set
+pub fn get_http_port() -> Option<u16> {
+  env::var("HTTP_PORT")
+    .ok()
+    .and_then(|value| value.parse::<u16>().ok())
+}
+
+/// Directory where the job workspaces are created, they are disabled if it is not set
+pub fn get_workspace_root() -> Option<String> {
+  env::var("WORKSPACE_ROOT").ok()
+}
+
+pub fn get_workspace_keep_on_failure() -> bool {
+  let value = get_env_value!("WORKSPACE_KEEP_ON_FAILURE", "false");
+  matches!(value.as_str(), "true" | "1" | "True" | "TRUE")
+}
+
+/// Age in seconds after which the workspaces of the other worker instances are removed,
+/// `0` disables their removal
+pub fn get_workspace_max_age() -> u64 {
+  let value = get_env_value!("WORKSPACE_MAX_AGE", "86400");
+  value.parse::<u64>().unwrap_or(86400)
+}
+
+/// Delay in milliseconds after which a job is aborted, `0` disables the timeout
+pub fn get_job_timeout() -> u64 {
+  let value = get_env_value!("JOB_TIMEOUT", "0");
+  value.parse::<u64>().unwrap_or(0)
+}
+
+pub fn get_amqp_reconnection_delay() -> u64 {
+  let value = get_env_value!("AMQP_RECONNECTION_DELAY", "1000");
+  value.parse::<u64>().unwrap_or(1000)
+}
+
+pub fn get_amqp_reconnection_max_delay() -> u64 {
+  let value = get_env_value!("AMQP_RECONNECTION_MAX_DELAY", "60000");
+  value.parse::<u64>().unwrap_or(60000)
+}
+
+pub fn get_amqp_reconnection_max_attempts() -> Option<u32> {
+  env::var("AMQP_RECONNECTION_MAX_ATTEMPTS")
+    .ok()
+    .and_then(|value| value.parse::<u32>().ok())
+    .filter(|max_attempts| *max_attempts > 0)
+}
+
+pub fn get_shutdown_grace_period() -> u64 {
+  let value = get_env_value!("SHUTDOWN_GRACE_PERIOD", "20");
+  value.parse::<u64>().unwrap_or(20)
+}
+
 pub fn get_store_hostname(store_code: &str) -> String {
   get_env_value!(
     &format!("{}_HOSTNAME", store_code),
@@ -58,6 +162,15 @@ pub fn get_store_password(store_code: &str) -> String {
   get_env_value!(&format!("{}_PASSWORD", store_code), "")
 }
 
+pub fn get_store_cache_ttl(store_code: &str) -> u64 {
+  let value = get_env_value!(&format!("{}_CACHE_TTL", store_code), "300");
+  value.parse::<u64>().unwrap_or(300)
+}
+
+pub fn get_secrets_directory() -> String {
+  get_env_value!("SECRETS_DIRECTORY", "/run/secrets")
+}
+
 pub fn get_amqp_uri() -> AMQPUri {
   let amqp_tls = get_amqp_tls();
   let amqp_hostname = get_amqp_hostname();
@@ -109,6 +222,10 @@ pub fn get_source_orders() -> Option<Vec<String>> {
     .unwrap_or(None)
 }
 
+pub fn get_source_orders_output() -> Option<String> {
+  env::var("SOURCE_ORDERS_OUTPUT").ok()
+}
+
 #[test]
 fn configuration() {
   assert!(get_amqp_tls() == true);
@@ -118,12 +235,53 @@ fn configuration() {
   assert!(get_amqp_password() == "guest".to_string());
   assert!(get_amqp_vhost() == "/".to_string());
   assert!(get_amqp_queue() == "job_undefined".to_string());
+  assert!(get_worker_concurrency() == 1);
+  assert!(get_shutdown_grace_period() == 20);
+  assert!(get_job_max_retries() == 10);
+  assert!(get_job_retry_delays() == vec![5000, 30000, 300000, 1800000]);
+  assert!(get_job_progression_interval() == 500);
+  assert!(get_job_timeout() == 0);
+  assert!(!get_job_isolation());
+  assert!(get_workspace_root().is_none());
+  assert!(get_http_port().is_none());
+  assert!(!get_log_json());
+  assert!(!get_workspace_keep_on_failure());
+  assert!(get_workspace_max_age() == 86400);
+  assert!(get_source_orders_output().is_none());
+  assert!(get_amqp_reconnection_delay() == 1000);
+  assert!(get_amqp_reconnection_max_delay() == 60000);
+  assert!(get_amqp_reconnection_max_attempts().is_none());
   assert!(get_store_hostname("BACKEND") == "http://127.0.0.1:4000/api".to_string());
   assert!(get_store_username("BACKEND") == "".to_string());
   assert!(get_store_password("BACKEND") == "".to_string());
+  assert!(get_store_cache_ttl("BACKEND") == 300);
+  assert!(get_secrets_directory() == "/run/secrets");
 
   env::set_var("AMQP_TLS", "False");
   assert!(get_amqp_tls() == false);
   env::set_var("AMQP_PORT", "BAD_VALUE");
   assert!(get_amqp_port() == 5672);
+  env::set_var("AMQP_RECONNECTION_MAX_ATTEMPTS", "10");
+  assert!(get_amqp_reconnection_max_attempts() == Some(10));
+  env::set_var("AMQP_RECONNECTION_MAX_ATTEMPTS", "0");
+  assert!(get_amqp_reconnection_max_attempts().is_none());
+  env::remove_var("AMQP_RECONNECTION_MAX_ATTEMPTS");
+  env::set_var("JOB_RETRY_DELAYS", "1000, 2000,BAD_VALUE");
+  assert!(get_job_retry_delays() == vec![1000, 2000]);
+  env::set_var("JOB_RETRY_DELAYS", "BAD_VALUE");
+  assert!(get_job_retry_delays() == vec![5000, 30000, 300000, 1800000]);
+  env::remove_var("JOB_RETRY_DELAYS");
+  env::set_var("SHUTDOWN_GRACE_PERIOD", "5");
+  assert!(get_shutdown_grace_period() == 5);
+  env::set_var("SHUTDOWN_GRACE_PERIOD", "BAD_VALUE");
+  assert!(get_shutdown_grace_period() == 20);
+  env::remove_var("SHUTDOWN_GRACE_PERIOD");
+  #[cfg(not(feature = "media"))]
+  {
+    env::set_var("WORKER_CONCURRENCY", "4");
+    assert!(get_worker_concurrency() == 4);
+    env::set_var("WORKER_CONCURRENCY", "0");
+    assert!(get_worker_concurrency() == 1);
+    env::remove_var("WORKER_CONCURRENCY");
+  }
 }
@@ -1,13 +1,16 @@
 use crate::job::{JobResult, JobStatus};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 /// Internal error status to manage process errors
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub enum MessageError {
   RuntimeError(String),
   ParameterValueError(String),
   ProcessingError(JobResult),
   RequirementsError(String),
   NotImplemented(),
+  JobError(JobError),
 }
 
 impl MessageError {
@@ -18,6 +21,98 @@ impl MessageError {
 
     MessageError::ProcessingError(result)
   }
+
+  /// Returns how the job message is handled for this error
+  pub fn get_kind(&self) -> ErrorKind {
+    match self {
+      MessageError::RuntimeError(_)
+      | MessageError::ProcessingError(_)
+      | MessageError::ParameterValueError(_)
+      | MessageError::NotImplemented() => ErrorKind::Permanent,
+      MessageError::RequirementsError(_) => ErrorKind::Requirements,
+      MessageError::JobError(job_error) => job_error.get_kind(),
+    }
+  }
+}
+
+impl From<JobError> for MessageError {
+  fn from(job_error: JobError) -> Self {
+    MessageError::JobError(job_error)
+  }
+}
+
+/// Classification of the errors
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+  /// The job is retried after a delay
+  Transient,
+  /// The job is published in error and acknowledged
+  Permanent,
+  /// The job is requeued to be processed by another worker instance
+  Requirements,
+}
+
+/// Error returned by a worker, with an optional code and structured details
+///
+/// The code and the details are published in the `job_error` payload.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct JobError {
+  kind: ErrorKind,
+  message: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  code: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  details: Option<Value>,
+}
+
+impl JobError {
+  pub fn new(kind: ErrorKind, message: &str) -> Self {
+    JobError {
+      kind,
+      message: message.to_string(),
+      code: None,
+      details: None,
+    }
+  }
+
+  pub fn transient(message: &str) -> Self {
+    JobError::new(ErrorKind::Transient, message)
+  }
+
+  pub fn permanent(message: &str) -> Self {
+    JobError::new(ErrorKind::Permanent, message)
+  }
+
+  pub fn requirements(message: &str) -> Self {
+    JobError::new(ErrorKind::Requirements, message)
+  }
+
+  pub fn with_code(mut self, code: &str) -> Self {
+    self.code = Some(code.to_string());
+    self
+  }
+
+  pub fn with_details<T: Serialize>(mut self, details: &T) -> Self {
+    self.details = serde_json::to_value(details).ok();
+    self
+  }
+
+  pub fn get_kind(&self) -> ErrorKind {
+    self.kind
+  }
+
+  pub fn get_message(&self) -> &str {
+    &self.message
+  }
+
+  pub fn get_code(&self) -> Option<&String> {
+    self.code.as_ref()
+  }
+
+  pub fn get_details(&self) -> Option<&Value> {
+    self.details.as_ref()
+  }
 }
 
 pub type Result<T> = std::result::Result<T, MessageError>;
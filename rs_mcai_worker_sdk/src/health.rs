@@ -0,0 +1,143 @@
+//! State of the worker, reported by the liveness and readiness probes
+
+use futures::{channel::mpsc, future::ready, StreamExt};
+use serde_json::Value;
+use std::{
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Mutex,
+  },
+  thread,
+  time::{Duration, Instant},
+};
+
+static HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+/// Delay without heartbeat after which the worker is not alive anymore
+static LIVENESS_TIMEOUT: Duration = Duration::from_secs(30);
+
+static INITIALIZED: AtomicBool = AtomicBool::new(false);
+static CONNECTED: AtomicBool = AtomicBool::new(false);
+static CONSUMING: AtomicBool = AtomicBool::new(false);
+static DRAINING: AtomicBool = AtomicBool::new(false);
+static STALLED: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+  static ref LAST_HEARTBEAT: Mutex<Instant> = Mutex::new(Instant::now());
+}
+
+/// `MessageEvent::init` succeeded
+pub(crate) fn set_initialized() {
+  INITIALIZED.store(true, Ordering::SeqCst);
+}
+
+pub(crate) fn set_connected(connected: bool) {
+  CONNECTED.store(connected, Ordering::SeqCst);
+}
+
+/// The consumer of the jobs queue is registered
+pub(crate) fn set_consuming(consuming: bool) {
+  CONSUMING.store(consuming, Ordering::SeqCst);
+}
+
+/// The worker waits for its running jobs before terminating
+pub(crate) fn set_draining() {
+  DRAINING.store(true, Ordering::SeqCst);
+}
+
+/// A timed out job is still running after its grace period, it may hold the worker forever
+pub(crate) fn set_stalled() {
+  STALLED.store(true, Ordering::SeqCst);
+}
+
+pub(crate) fn is_stalled() -> bool {
+  STALLED.load(Ordering::SeqCst)
+}
+
+pub(crate) fn heartbeat() {
+  if let Ok(mut last_heartbeat) = LAST_HEARTBEAT.lock() {
+    *last_heartbeat = Instant::now();
+  }
+}
+
+/// Record a heartbeat every second, as long as the event loop runs this future
+pub(crate) async fn beat() {
+  let (sender, receiver) = mpsc::unbounded::<()>();
+
+  thread::spawn(move || {
+    while sender.unbounded_send(()).is_ok() {
+      thread::sleep(HEARTBEAT_INTERVAL);
+    }
+  });
+
+  receiver
+    .for_each(|_| {
+      heartbeat();
+      ready(())
+    })
+    .await
+}
+
+/// Returns whether the worker is alive, with the details of the check
+///
+/// The event loop only starts after `MessageEvent::init`, a worker is alive while it initializes.
+pub(crate) fn get_liveness() -> (bool, Value) {
+  if !INITIALIZED.load(Ordering::SeqCst) {
+    return (true, json!({ "status": "initializing" }));
+  }
+
+  let elapsed = LAST_HEARTBEAT
+    .lock()
+    .map(|last_heartbeat| last_heartbeat.elapsed())
+    .unwrap_or(LIVENESS_TIMEOUT);
+
+  let alive = elapsed < LIVENESS_TIMEOUT;
+  let details = json!({
+    "status": if alive { "alive" } else { "stalled" },
+    "last_heartbeat_ms": elapsed.as_millis() as u64,
+  });
+  (alive, details)
+}
+
+/// Returns whether the worker is ready to process jobs, with the details of the checks
+pub(crate) fn get_readiness() -> (bool, Value) {
+  let initialized = INITIALIZED.load(Ordering::SeqCst);
+  let connected = CONNECTED.load(Ordering::SeqCst);
+  let consuming = CONSUMING.load(Ordering::SeqCst);
+  let draining = DRAINING.load(Ordering::SeqCst);
+  let stalled = STALLED.load(Ordering::SeqCst);
+
+  let ready = initialized && connected && consuming && !draining && !stalled;
+  let details = json!({
+    "status": if ready { "ready" } else { "not_ready" },
+    "checks": {
+      "initialized": initialized,
+      "connected": connected,
+      "consuming": consuming,
+      "draining": draining,
+      "stalled": stalled,
+    }
+  });
+  (ready, details)
+}
+
+#[test]
+fn health_checks() {
+  set_initialized();
+  heartbeat();
+  let (alive, details) = get_liveness();
+  assert!(alive);
+  assert_eq!(details["status"], json!("alive"));
+
+  set_connected(true);
+  set_consuming(false);
+  let (ready, details) = get_readiness();
+  assert!(!ready);
+  assert_eq!(details["checks"]["initialized"], json!(true));
+  assert_eq!(details["checks"]["consuming"], json!(false));
+
+  set_consuming(true);
+  assert!(get_readiness().0);
+
+  set_connected(false);
+  set_consuming(false);
+}
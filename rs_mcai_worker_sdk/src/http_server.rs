@@ -0,0 +1,141 @@
+//! Embedded HTTP server, exposing the metrics and the health probes of the worker
+
+use crate::{health, metrics};
+use serde_json::Value;
+use std::{
+  io::{self, BufRead, BufReader, Write},
+  net::{SocketAddr, TcpListener, TcpStream},
+  thread,
+  time::Duration,
+};
+
+/// Delay to read a request or write a response, so a slow client does not block the probes
+static CONNECTION_TIMEOUT: Duration = Duration::from_secs(5);
+
+struct Response {
+  status: &'static str,
+  content_type: &'static str,
+  body: String,
+}
+
+/// Start the server in a background thread, returns its address
+pub fn start(port: u16) -> io::Result<SocketAddr> {
+  let listener = TcpListener::bind(("0.0.0.0", port))?;
+  let address = listener.local_addr()?;
+
+  thread::Builder::new()
+    .name("http_server".to_string())
+    .spawn(move || {
+      for stream in listener.incoming() {
+        match stream {
+          Ok(stream) => {
+            if let Err(error) = handle_connection(stream) {
+              debug!("Unable to answer HTTP request: {}", error);
+            }
+          }
+          Err(error) => warn!("Unable to accept HTTP connection: {}", error),
+        }
+      }
+    })?;
+
+  Ok(address)
+}
+
+fn handle_connection(mut stream: TcpStream) -> io::Result<()> {
+  stream.set_read_timeout(Some(CONNECTION_TIMEOUT))?;
+  stream.set_write_timeout(Some(CONNECTION_TIMEOUT))?;
+
+  let mut request_line = String::new();
+  let mut reader = BufReader::new(stream.try_clone()?);
+  reader.read_line(&mut request_line)?;
+
+  // the headers are not used, but they are read to not reset the connection
+  let mut header = String::new();
+  while reader.read_line(&mut header)? > 2 {
+    header.clear();
+  }
+
+  let mut parts = request_line.split_whitespace();
+  let response = match (parts.next(), parts.next()) {
+    (Some("GET"), Some(path)) => route(path),
+    _ => Response {
+      status: "405 Method Not Allowed",
+      content_type: "text/plain",
+      body: "Method not allowed\n".to_string(),
+    },
+  };
+
+  write!(
+    stream,
+    "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+    response.status,
+    response.content_type,
+    response.body.len(),
+    response.body
+  )?;
+  stream.flush()
+}
+
+fn route(path: &str) -> Response {
+  match path {
+    "/metrics" => Response {
+      status: "200 OK",
+      content_type: "text/plain; version=0.0.4",
+      body: metrics::render(),
+    },
+    "/health/live" => probe(health::get_liveness()),
+    "/health/ready" => probe(health::get_readiness()),
+    _ => Response {
+      status: "404 Not Found",
+      content_type: "text/plain",
+      body: "Not found\n".to_string(),
+    },
+  }
+}
+
+fn probe((healthy, details): (bool, Value)) -> Response {
+  Response {
+    status: if healthy {
+      "200 OK"
+    } else {
+      "503 Service Unavailable"
+    },
+    content_type: "application/json",
+    body: details.to_string(),
+  }
+}
+
+#[cfg(test)]
+fn request(address: SocketAddr, request: &str) -> String {
+  use std::io::Read;
+
+  let mut stream = TcpStream::connect(address).unwrap();
+  write!(stream, "{}", request).unwrap();
+  let mut response = String::new();
+  stream.read_to_string(&mut response).unwrap();
+  response
+}
+
+#[test]
+fn http_server_routes() {
+  let address = start(0).unwrap();
+  let address = SocketAddr::from(([127, 0, 0, 1], address.port()));
+
+  let response = request(address, "GET /metrics HTTP/1.1\r\nHost: worker\r\n\r\n");
+  assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+  assert!(response.contains("Content-Type: text/plain; version=0.0.4\r\n"));
+  assert!(response.contains("# TYPE mcai_jobs_received_total counter\n"));
+
+  health::set_initialized();
+  health::heartbeat();
+  let response = request(address, "GET /health/live HTTP/1.1\r\n\r\n");
+  assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+  assert!(response.contains("Content-Type: application/json\r\n"));
+  assert!(response.contains("\"status\":\"alive\""));
+
+  let response = request(address, "GET /unknown HTTP/1.1\r\n\r\n");
+  assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+
+  let response = request(address, "POST /metrics HTTP/1.1\r\n\r\n");
+  assert!(response.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+}
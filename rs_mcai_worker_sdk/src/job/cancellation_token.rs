@@ -0,0 +1,60 @@
+use std::sync::{
+  atomic::{AtomicBool, Ordering},
+  Arc,
+};
+
+/// Shared flag to request the interruption of a running job
+///
+/// Clones share the same state, so the worker can cancel the job while it is processed.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+  cancelled: Arc<AtomicBool>,
+  parent: Option<Box<CancellationToken>>,
+}
+
+impl CancellationToken {
+  /// Token cancelled with this one, that can also be cancelled on its own
+  pub fn child(&self) -> Self {
+    CancellationToken {
+      cancelled: Arc::new(AtomicBool::new(false)),
+      parent: Some(Box::new(self.clone())),
+    }
+  }
+
+  pub fn cancel(&self) {
+    self.cancelled.store(true, Ordering::SeqCst);
+  }
+
+  pub fn is_cancelled(&self) -> bool {
+    self.cancelled.load(Ordering::SeqCst)
+      || self
+        .parent
+        .as_ref()
+        .map(|parent| parent.is_cancelled())
+        .unwrap_or(false)
+  }
+}
+
+#[test]
+pub fn test_cancellation_token() {
+  let token = CancellationToken::default();
+  let shared_token = token.clone();
+  assert!(!token.is_cancelled());
+
+  shared_token.cancel();
+  assert!(token.is_cancelled());
+  assert!(!CancellationToken::default().is_cancelled());
+}
+
+#[test]
+pub fn test_child_cancellation_token() {
+  let token = CancellationToken::default();
+  let child = token.child();
+  child.cancel();
+  assert!(child.is_cancelled());
+  assert!(!token.is_cancelled());
+
+  let child = token.child();
+  token.cancel();
+  assert!(child.is_cancelled());
+}
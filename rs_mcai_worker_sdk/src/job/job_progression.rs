@@ -1,5 +1,6 @@
 use crate::worker::docker::get_instance_id;
 use chrono::prelude::*;
+use std::time::Duration;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct JobProgression {
@@ -7,6 +8,19 @@ pub struct JobProgression {
   docker_container_id: String,
   job_id: u64,
   progression: u8,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  step: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  step_index: Option<u32>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  step_count: Option<u32>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  processed_units: Option<u64>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  message: Option<String>,
+  /// Estimated remaining time in seconds
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  estimated_remaining_time: Option<f64>,
 }
 
 impl JobProgression {
@@ -16,8 +30,49 @@ impl JobProgression {
       docker_container_id: get_instance_id("/proc/self/cgroup"),
       job_id,
       progression,
+      step: None,
+      step_index: None,
+      step_count: None,
+      processed_units: None,
+      message: None,
+      estimated_remaining_time: None,
     }
   }
+
+  /// Set the current step, with its index starting at 0 and the number of steps
+  pub fn with_step(mut self, name: &str, index: u32, count: u32) -> Self {
+    self.step = Some(name.to_string());
+    self.step_index = Some(index);
+    self.step_count = Some(count);
+    self
+  }
+
+  pub fn with_processed_units(mut self, processed_units: u64) -> Self {
+    self.processed_units = Some(processed_units);
+    self
+  }
+
+  pub fn with_message(mut self, message: &str) -> Self {
+    self.message = Some(message.to_string());
+    self
+  }
+
+  pub fn with_estimated_remaining_time(mut self, remaining_time: Duration) -> Self {
+    self.estimated_remaining_time = Some(remaining_time.as_secs_f64());
+    self
+  }
+
+  pub fn get_job_id(&self) -> u64 {
+    self.job_id
+  }
+
+  pub fn get_progression(&self) -> u8 {
+    self.progression
+  }
+
+  pub fn get_step_index(&self) -> Option<u32> {
+    self.step_index
+  }
 }
 
 #[test]
@@ -37,3 +92,29 @@ pub fn test_job_progression() {
   );
   assert!(!job_progression.docker_container_id.is_empty());
 }
+
+#[test]
+pub fn test_job_progression_details() {
+  let job_progression = JobProgression::new(123, 40);
+  let serialized = serde_json::to_value(&job_progression).unwrap();
+  assert!(serialized.get("step").is_none());
+  assert!(serialized.get("estimated_remaining_time").is_none());
+
+  let job_progression = JobProgression::new(123, 40)
+    .with_step("transcoding", 1, 3)
+    .with_processed_units(2500)
+    .with_message("Transcoding video track")
+    .with_estimated_remaining_time(Duration::from_millis(12500));
+
+  assert_eq!(job_progression.get_step_index(), Some(1));
+
+  let serialized = serde_json::to_value(&job_progression).unwrap();
+  assert_eq!(serialized["job_id"], json!(123));
+  assert_eq!(serialized["progression"], json!(40));
+  assert_eq!(serialized["step"], json!("transcoding"));
+  assert_eq!(serialized["step_index"], json!(1));
+  assert_eq!(serialized["step_count"], json!(3));
+  assert_eq!(serialized["processed_units"], json!(2500));
+  assert_eq!(serialized["message"], json!("Transcoding video track"));
+  assert_eq!(serialized["estimated_remaining_time"], json!(12.5));
+}
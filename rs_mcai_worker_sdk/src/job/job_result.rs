@@ -1,17 +1,20 @@
-use super::job_status::JobStatus;
+use super::{cancellation_token::CancellationToken, job_status::JobStatus};
 use crate::job::Job;
 use crate::parameter::container::ParametersContainer;
 use crate::parameter::Parameter;
 use crate::parameter::ParameterValue;
 use reqwest::Error;
-use serde::Serialize;
+use serde::{Serialize, Serializer};
 use std::time::Instant;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct JobResult {
+  #[serde(skip_serializing, skip_deserializing)]
+  cancellation_token: CancellationToken,
   destination_paths: Vec<String>,
   execution_duration: f64,
   job_id: u64,
+  #[serde(serialize_with = "serialize_redacted_parameters")]
   parameters: Vec<Parameter>,
   #[serde(skip_serializing, skip_deserializing, default = "default_instant")]
   start_instant: Instant,
@@ -22,9 +25,17 @@ fn default_instant() -> Instant {
   Instant::now()
 }
 
+fn serialize_redacted_parameters<S: Serializer>(
+  parameters: &[Parameter],
+  serializer: S,
+) -> Result<S::Ok, S::Error> {
+  serializer.collect_seq(parameters.iter().map(Parameter::redacted))
+}
+
 impl JobResult {
   pub fn new(job_id: u64) -> JobResult {
     JobResult {
+      cancellation_token: CancellationToken::default(),
       destination_paths: vec![],
       execution_duration: 0.0,
       job_id,
@@ -48,6 +59,7 @@ impl JobResult {
       store: None,
       default: None,
       value: serde_json::to_value(error.to_string()).ok(),
+      template: false,
     });
     self
   }
@@ -59,10 +71,16 @@ impl JobResult {
       store: None,
       default: None,
       value: serde_json::to_value(message.to_string()).ok(),
+      template: false,
     });
     self
   }
 
+  pub fn with_cancellation_token(mut self, cancellation_token: CancellationToken) -> Self {
+    self.cancellation_token = cancellation_token;
+    self
+  }
+
   pub fn with_parameters(mut self, parameters: &mut Vec<Parameter>) -> Self {
     self.parameters.append(parameters);
     self
@@ -83,6 +101,7 @@ impl JobResult {
       store: None,
       default: None,
       value: serde_json::to_value(serializable).ok(),
+      template: false,
     });
     Ok(self)
   }
@@ -99,6 +118,17 @@ impl JobResult {
     &self.status
   }
 
+  pub fn get_cancellation_token(&self) -> &CancellationToken {
+    &self.cancellation_token
+  }
+
+  /// Returns `true` when the job has been requested to stop
+  ///
+  /// Long processes should check it regularly, and return as soon as possible.
+  pub fn is_cancelled(&self) -> bool {
+    self.cancellation_token.is_cancelled()
+  }
+
   pub fn get_execution_duration(&self) -> f64 {
     self.execution_duration
   }
@@ -2,10 +2,20 @@
 pub enum JobStatus {
   #[serde(rename = "unknown")]
   Unknown,
+  #[serde(rename = "initializing")]
+  Initializing,
+  #[serde(rename = "running")]
+  Running,
   #[serde(rename = "completed")]
   Completed,
   #[serde(rename = "error")]
   Error,
+  #[serde(rename = "stopped")]
+  Stopped,
+  #[serde(rename = "skipped")]
+  Skipped,
+  #[serde(rename = "paused")]
+  Paused,
 }
 
 impl Default for JobStatus {
@@ -18,8 +28,18 @@ impl Default for JobStatus {
 pub fn test_job_status_json() {
   let json = serde_json::to_string(&JobStatus::Unknown).unwrap();
   assert_eq!("\"unknown\"", &json);
+  let json = serde_json::to_string(&JobStatus::Initializing).unwrap();
+  assert_eq!("\"initializing\"", &json);
+  let json = serde_json::to_string(&JobStatus::Running).unwrap();
+  assert_eq!("\"running\"", &json);
   let json = serde_json::to_string(&JobStatus::Completed).unwrap();
   assert_eq!("\"completed\"", &json);
   let json = serde_json::to_string(&JobStatus::Error).unwrap();
   assert_eq!("\"error\"", &json);
+  let json = serde_json::to_string(&JobStatus::Stopped).unwrap();
+  assert_eq!("\"stopped\"", &json);
+  let json = serde_json::to_string(&JobStatus::Skipped).unwrap();
+  assert_eq!("\"skipped\"", &json);
+  let json = serde_json::to_string(&JobStatus::Paused).unwrap();
+  assert_eq!("\"paused\"", &json);
 }
@@ -0,0 +1,57 @@
+use super::job_status::JobStatus;
+use crate::worker::docker::get_instance_id;
+use chrono::prelude::*;
+
+/// Transition of a job from a status to another one
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JobStatusUpdate {
+  datetime: DateTime<Utc>,
+  docker_container_id: String,
+  job_id: u64,
+  previous_status: Option<JobStatus>,
+  status: JobStatus,
+}
+
+impl JobStatusUpdate {
+  pub fn new(job_id: u64, previous_status: Option<JobStatus>, status: JobStatus) -> Self {
+    JobStatusUpdate {
+      datetime: Utc::now(),
+      docker_container_id: get_instance_id("/proc/self/cgroup"),
+      job_id,
+      previous_status,
+      status,
+    }
+  }
+
+  pub fn get_job_id(&self) -> u64 {
+    self.job_id
+  }
+
+  pub fn get_previous_status(&self) -> Option<&JobStatus> {
+    self.previous_status.as_ref()
+  }
+
+  pub fn get_status(&self) -> &JobStatus {
+    &self.status
+  }
+}
+
+#[test]
+pub fn test_job_status_update() {
+  let job_status_update =
+    JobStatusUpdate::new(123, Some(JobStatus::Initializing), JobStatus::Running);
+
+  assert_eq!(job_status_update.get_job_id(), 123);
+  assert_eq!(
+    job_status_update.get_previous_status(),
+    Some(&JobStatus::Initializing)
+  );
+  assert_eq!(job_status_update.get_status(), &JobStatus::Running);
+  assert!(!job_status_update.docker_container_id.is_empty());
+
+  let serialized = serde_json::to_value(&job_status_update).unwrap();
+  assert_eq!(serialized["job_id"], json!(123));
+  assert_eq!(serialized["previous_status"], json!("initializing"));
+  assert_eq!(serialized["status"], json!("running"));
+  assert!(serialized["datetime"].is_string());
+}
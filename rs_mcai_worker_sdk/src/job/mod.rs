@@ -1,20 +1,37 @@
 //! Module to manage Job
 
-use crate::{parameter::container::ParametersContainer, MessageError, Parameter, Requirement};
+use crate::{
+  parameter::container::ParametersContainer, JobError, MessageError, Parameter, ParameterValue,
+  Requirement,
+};
 use serde_json::{Map, Value};
-use std::path::Path;
 
+mod cancellation_token;
 mod job_progression;
 mod job_result;
 mod job_status;
+mod job_status_update;
+mod workspace;
 
-use crate::parameter::store::request_value;
-use crate::Result;
+use crate::parameter::{
+  redact, store::request_value, template, validation, ParameterViolation, REDACTED_VALUE,
+};
+use crate::{config::get_job_timeout, Result};
+pub use cancellation_token::CancellationToken;
 pub use job_progression::JobProgression;
 pub use job_result::JobResult;
 pub use job_status::JobStatus;
+pub use job_status_update::JobStatusUpdate;
+use schemars::{schema_for, JsonSchema};
 use serde::de::DeserializeOwned;
 use serde::Deserialize;
+use std::{path::Path, time::Duration};
+pub use workspace::Workspace;
+
+/// Reserved parameter to override the timeout of a job, in milliseconds
+pub static TIMEOUT_PARAMETER: &str = "sdk_timeout_ms";
+/// Reserved parameter set with the path of the job workspace
+pub static WORKSPACE_PARAMETER: &str = "sdk_workspace";
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Job {
@@ -51,6 +68,14 @@ pub struct ValueResponseBody {
   pub data: DataResponseBody,
 }
 
+fn get_violations_message(violations: &[ParameterViolation]) -> String {
+  let violations: Vec<String> = violations
+    .iter()
+    .map(|violation| violation.to_string())
+    .collect();
+  format!("Invalid parameters: {}", violations.join("; "))
+}
+
 impl Job {
   pub fn new(message: &str) -> Result<Self> {
     let parsed: std::result::Result<Job, _> = serde_json::from_str(message);
@@ -59,6 +84,88 @@ impl Job {
   }
 
   pub fn get_parameters<P: Sized + DeserializeOwned>(&self) -> Result<P> {
+    let parameters = self.get_parameters_values()?;
+    self.deserialize_parameters(parameters)
+  }
+
+  /// Get the parameters, once validated against the JSON schema of `P`
+  ///
+  /// On invalid parameters, a `ParameterValueError` lists all the violations.
+  pub fn get_validated_parameters<P: Sized + DeserializeOwned + JsonSchema>(&self) -> Result<P> {
+    let parameters = self.get_parameters_values()?;
+
+    let violations = self.get_violations::<P>(&parameters);
+    if !violations.is_empty() {
+      return Err(MessageError::ParameterValueError(get_violations_message(
+        &violations,
+      )));
+    }
+
+    self.deserialize_parameters(parameters)
+  }
+
+  /// Check the parameters against the JSON schema of `P`, returning all the violations
+  pub fn validate_parameters<P: JsonSchema>(&self) -> Result<Vec<ParameterViolation>> {
+    let parameters = self.get_parameters_values()?;
+    Ok(self.get_violations::<P>(&parameters))
+  }
+
+  /// Get the validated parameters of a processed job
+  ///
+  /// On invalid parameters, the violations are the details of the published `parameter_value_error`.
+  pub(crate) fn get_processed_parameters<P: Sized + DeserializeOwned + JsonSchema>(
+    &self,
+  ) -> Result<P> {
+    let parameters = self.get_parameters_values()?;
+
+    let violations = self.get_violations::<P>(&parameters);
+    if !violations.is_empty() {
+      let job_error = JobError::permanent(&get_violations_message(&violations))
+        .with_code("parameter_value_error")
+        .with_details(&violations);
+      return Err(job_error.into());
+    }
+
+    self.deserialize_parameters(parameters)
+  }
+
+  fn get_violations<P: JsonSchema>(&self, parameters: &Value) -> Vec<ParameterViolation> {
+    validation::validate(
+      &schema_for!(P),
+      parameters,
+      &self.get_secret_parameters_ids(),
+    )
+  }
+
+  fn get_secret_parameters_ids(&self) -> Vec<String> {
+    self
+      .parameters
+      .iter()
+      .filter(|parameter| parameter.is_secret())
+      .map(|parameter| parameter.id.clone())
+      .collect()
+  }
+
+  fn deserialize_parameters<P: Sized + DeserializeOwned>(&self, parameters: Value) -> Result<P> {
+    serde_json::from_value(parameters.clone()).map_err(|error| {
+      let mut redacted_parameters = parameters.clone();
+      let mut secrets = vec![];
+      for id in self.get_secret_parameters_ids() {
+        if let Some(value) = redacted_parameters.get_mut(&id) {
+          secrets.push(value.clone());
+          *value = Value::String(REDACTED_VALUE.to_string());
+        }
+      }
+
+      let message = format!(
+        "Cannot get parameters from {:?}: {:?}",
+        redacted_parameters, error
+      );
+      MessageError::ParameterValueError(redact(&message, &secrets.iter().collect::<Vec<_>>()))
+    })
+  }
+
+  fn get_parameters_values(&self) -> Result<Value> {
     let mut parameters = Map::<String, Value>::new();
     for parameter in &self.parameters {
       if let Some(value) = parameter
@@ -88,29 +195,97 @@ impl Job {
         parameters.insert(parameter.id.clone(), value);
       }
     }
-    let parameters = serde_json::Value::Object(parameters);
 
-    serde_json::from_value(parameters.clone()).map_err(|error| {
-      MessageError::ParameterValueError(format!(
-        "Cannot get parameters from {:?}: {:?}",
-        parameters, error
-      ))
-    })
+    self.resolve_templates(&mut parameters)?;
+    Ok(serde_json::Value::Object(parameters))
+  }
+
+  fn resolve_templates(&self, parameters: &mut Map<String, Value>) -> Result<()> {
+    let templates: Vec<String> = self
+      .parameters
+      .iter()
+      .filter(|parameter| parameter.template && !parameter.is_secret())
+      .map(|parameter| parameter.id.clone())
+      .collect();
+
+    template::resolve(
+      parameters,
+      self.job_id,
+      &templates,
+      &self.get_secret_parameters_ids(),
+    )
+  }
+
+  /// Get a parameter, with its placeholders resolved if it is declared as a template
+  ///
+  /// Unlike `get_parameter`, the templated parameters get the same value as in `get_parameters`.
+  pub fn get_templated_parameter<T: DeserializeOwned + ParameterValue>(
+    &self,
+    key: &str,
+  ) -> Result<T> {
+    let templated = self.parameters.iter().any(|parameter| {
+      parameter.id == key
+        && parameter.kind == T::get_type_as_string()
+        && parameter.template
+        && !parameter.is_secret()
+    });
+    if !templated {
+      return self.get_parameter(key);
+    }
+
+    // the secret parameters can not be referenced, their value is not requested
+    let mut parameters: Map<String, Value> = self
+      .parameters
+      .iter()
+      .filter(|parameter| !parameter.is_secret())
+      .filter_map(|parameter| {
+        parameter
+          .value
+          .clone()
+          .or_else(|| parameter.default.clone())
+          .map(|value| (parameter.id.clone(), value))
+      })
+      .collect();
+    self.resolve_templates(&mut parameters)?;
+
+    match parameters.remove(key) {
+      Some(value) => T::from_value(value),
+      None => self.get_parameter(key),
+    }
+  }
+
+  /// Delay after which the processing of the job is aborted
+  ///
+  /// The `sdk_timeout_ms` parameter overrides the `JOB_TIMEOUT` of the worker, `0` disables the timeout.
+  pub fn get_timeout(&self) -> Option<Duration> {
+    let timeout = self
+      .get_parameter::<u64>(TIMEOUT_PARAMETER)
+      .unwrap_or_else(|_| get_job_timeout());
+
+    Some(timeout)
+      .filter(|timeout| *timeout > 0)
+      .map(Duration::from_millis)
+  }
+
+  /// Set the path of the job workspace in the `sdk_workspace` parameter
+  pub fn set_workspace(&mut self, workspace: &Path) {
+    self
+      .parameters
+      .retain(|parameter| parameter.id != WORKSPACE_PARAMETER);
+
+    self.parameters.push(Parameter {
+      id: WORKSPACE_PARAMETER.to_string(),
+      kind: String::get_type_as_string(),
+      store: None,
+      default: None,
+      value: Some(Value::String(workspace.to_string_lossy().to_string())),
+      template: false,
+    });
   }
 
   pub fn check_requirements(&self) -> Result<()> {
     if let Ok(requirements) = self.get_parameter::<Requirement>("requirements") {
-      if let Some(paths) = requirements.paths {
-        for path in paths.iter() {
-          let p = Path::new(path);
-          if !p.exists() {
-            return Err(MessageError::RequirementsError(format!(
-              "Warning: Required file does not exists: {:?}",
-              p
-            )));
-          }
-        }
-      }
+      requirements.check()?;
     }
     Ok(())
   }
@@ -0,0 +1,189 @@
+use rand::Rng;
+use std::{
+  fs::{self, DirEntry},
+  io,
+  path::{Path, PathBuf},
+  time::Duration,
+};
+
+static WORKSPACE_PREFIX: &str = "job_";
+
+/// Temporary directory of a job, removed once its result is published
+///
+/// The workspaces are created in a directory dedicated to the worker instance,
+/// as the remaining ones are removed when the worker starts. The ones of the previous
+/// instances are removed once they are older than `WORKSPACE_MAX_AGE`.
+#[derive(Debug)]
+pub struct Workspace {
+  path: PathBuf,
+}
+
+impl Workspace {
+  /// Create a unique workspace for the job in the root directory
+  pub fn create(root: &Path, job_id: u64) -> io::Result<Self> {
+    fs::create_dir_all(root)?;
+
+    loop {
+      let suffix: u32 = rand::thread_rng().gen();
+      let path = root.join(format!("{}{}_{:08x}", WORKSPACE_PREFIX, job_id, suffix));
+
+      match fs::create_dir(&path) {
+        Ok(()) => return Ok(Workspace { path }),
+        Err(error) if error.kind() == io::ErrorKind::AlreadyExists => continue,
+        Err(error) => return Err(error),
+      }
+    }
+  }
+
+  pub fn get_path(&self) -> &Path {
+    &self.path
+  }
+
+  /// Remove the workspace, unless the job failed and the failed workspaces are kept
+  pub fn clean(self, failed: bool, keep_on_failure: bool) {
+    if failed && keep_on_failure {
+      info!("Keep the workspace of the failed job: {:?}", self.path);
+      return;
+    }
+
+    if let Err(error) = fs::remove_dir_all(&self.path) {
+      error!("Unable to remove the workspace {:?}: {}", self.path, error);
+    }
+  }
+
+  /// Remove the workspaces remaining in the root directory, like the ones of a crashed worker
+  pub fn sweep(root: &Path) {
+    remove_workspaces(root, None);
+  }
+
+  /// Remove the workspaces older than `max_age` in the directories of the other instances
+  ///
+  /// A restarted worker gets a new instance identifier, the workspaces of the previous instance
+  /// are only removed by this sweep. The directories of the instances are removed once empty.
+  pub fn sweep_other_instances(root: &Path, instance_directory: &Path, max_age: Duration) {
+    for entry in list_directory(root) {
+      let path = entry.path();
+      if !path.is_dir() || path == instance_directory {
+        continue;
+      }
+
+      remove_workspaces(&path, Some(max_age));
+      if is_older(&entry, max_age) && fs::remove_dir(&path).is_ok() {
+        info!(
+          "Remove the workspaces directory of a previous instance {:?}",
+          path
+        );
+      }
+    }
+  }
+}
+
+fn list_directory(directory: &Path) -> Vec<DirEntry> {
+  match fs::read_dir(directory) {
+    Ok(entries) => entries.filter_map(|entry| entry.ok()).collect(),
+    Err(error) if error.kind() == io::ErrorKind::NotFound => vec![],
+    Err(error) => {
+      error!(
+        "Unable to list the workspaces of {:?}: {}",
+        directory, error
+      );
+      vec![]
+    }
+  }
+}
+
+/// Remove the workspaces of the directory, only the ones older than `max_age` if it is set
+fn remove_workspaces(directory: &Path, max_age: Option<Duration>) {
+  for entry in list_directory(directory) {
+    let path = entry.path();
+    let is_workspace = path.is_dir()
+      && entry
+        .file_name()
+        .to_str()
+        .map(|name| name.starts_with(WORKSPACE_PREFIX))
+        .unwrap_or(false);
+
+    let is_stale = max_age
+      .map(|max_age| is_older(&entry, max_age))
+      .unwrap_or(true);
+
+    if is_workspace && is_stale {
+      info!("Remove stale workspace {:?}", path);
+      if let Err(error) = fs::remove_dir_all(&path) {
+        error!("Unable to remove the workspace {:?}: {}", path, error);
+      }
+    }
+  }
+}
+
+/// Whether the entry was not modified since `max_age`
+fn is_older(entry: &DirEntry, max_age: Duration) -> bool {
+  entry
+    .metadata()
+    .and_then(|metadata| metadata.modified())
+    .ok()
+    .and_then(|modified| modified.elapsed().ok())
+    .map(|age| age >= max_age)
+    .unwrap_or(false)
+}
+
+#[test]
+fn workspaces() {
+  let root = std::env::temp_dir().join(format!("mcai_workspaces_{}", std::process::id()));
+
+  let workspace = Workspace::create(&root, 123).unwrap();
+  let other_workspace = Workspace::create(&root, 123).unwrap();
+  assert!(workspace.get_path().is_dir());
+  assert!(workspace.get_path().starts_with(&root));
+  assert_ne!(workspace.get_path(), other_workspace.get_path());
+
+  fs::write(workspace.get_path().join("output.txt"), "content").unwrap();
+  let path = workspace.get_path().to_path_buf();
+  workspace.clean(false, true);
+  assert!(!path.exists());
+
+  let path = other_workspace.get_path().to_path_buf();
+  other_workspace.clean(true, true);
+  assert!(path.exists());
+
+  let stale_workspace = Workspace::create(&root, 456).unwrap();
+  let stale_path = stale_workspace.get_path().to_path_buf();
+  let other_directory = root.join("other");
+  fs::create_dir_all(&other_directory).unwrap();
+
+  Workspace::sweep(&root);
+  assert!(!path.exists());
+  assert!(!stale_path.exists());
+  assert!(other_directory.exists());
+
+  fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn workspaces_of_other_instances() {
+  let root = std::env::temp_dir().join(format!("mcai_instances_{}", std::process::id()));
+  let instance_directory = root.join("current_instance");
+  let previous_instance_directory = root.join("previous_instance");
+
+  let workspace = Workspace::create(&instance_directory, 123).unwrap();
+  let previous_workspace = Workspace::create(&previous_instance_directory, 456).unwrap();
+  let other_file = previous_instance_directory.join("other.txt");
+  fs::write(&other_file, "content").unwrap();
+
+  // the workspaces are too recent
+  Workspace::sweep_other_instances(&root, &instance_directory, Duration::from_secs(3600));
+  assert!(previous_workspace.get_path().exists());
+
+  Workspace::sweep_other_instances(&root, &instance_directory, Duration::from_secs(0));
+  assert!(workspace.get_path().exists());
+  assert!(!previous_workspace.get_path().exists());
+  // the directory of the previous instance is only removed once empty
+  assert!(other_file.exists());
+
+  fs::remove_file(&other_file).unwrap();
+  Workspace::sweep_other_instances(&root, &instance_directory, Duration::from_secs(0));
+  assert!(!previous_instance_directory.exists());
+  assert!(instance_directory.exists());
+
+  fs::remove_dir_all(root).unwrap();
+}
@@ -40,6 +40,21 @@
 //! // }
 //! ```
 //!
+//! ## Command line
+//!
+//! Every worker started with [`start_worker`](fn.start_worker.html) accepts these subcommands:
+//!
+//! |       Subcommand        | Description |
+//! |-------------------------|-------------|
+//! | `consume`               | Consume the jobs of the AMQP queue (default) |
+//! | `describe`              | Print the worker configuration as JSON |
+//! | `schema`                | Print the JSON schema of the job parameters |
+//! | `run <order.json>...`   | Process local orders, without RabbitMQ |
+//! | `validate <order.json>` | Parse an order and check its parameters, without processing it |
+//!
+//! Their flags (like `--amqp-hostname` or `--concurrency`) override the environment variables below,
+//! run a subcommand with `--help` to list them.
+//!
 //! ## Runtime configuration
 //!
 //! ### AMQP connection
@@ -53,6 +68,26 @@
 //! | `AMQP_PASSWORD` | Password used to connect to AMQP server (default: `guest`) |
 //! | `AMQP_VHOST`    | AMQP virtual host (default: `/`) |
 //! | `AMQP_QUEUE`    | AMQP queue name used to receive job orders (default: `job_undefined`) |
+//! | `AMQP_RECONNECTION_DELAY` | Delay in milliseconds before the first reconnection attempt, doubled after each failure (default: `1000`) |
+//! | `AMQP_RECONNECTION_MAX_DELAY` | Maximum delay in milliseconds between two reconnection attempts (default: `60000`) |
+//! | `AMQP_RECONNECTION_MAX_ATTEMPTS` | Number of failed reconnection attempts before stopping the worker, an attempt succeeds once the jobs are consumed (default: unlimited) |
+//!
+//! ### Job processing
+//!
+//! |        Variable         | Description |
+//! |-------------------------|-------------|
+//! | `WORKER_CONCURRENCY`    | Number of jobs processed in parallel by the worker, not supported with the `media` feature (default: `1`) |
+//! | `SHUTDOWN_GRACE_PERIOD` | Delay in seconds to let running jobs finish on SIGTERM/SIGINT, before requeuing them (default: `20`) |
+//! | `JOB_MAX_RETRIES`       | Number of retries of a rejected job, before publishing it in error (default: `10`) |
+//! | `JOB_PROGRESSION_INTERVAL` | Minimum delay in milliseconds between two published progressions of a job (default: `500`) |
+//! | `JOB_RETRY_DELAYS`      | Comma separated delays in milliseconds before each retry of a job, the last one is used for the next retries (default: `5000,30000,300000,1800000`) |
+//! | `JOB_ISOLATION`         | Process each job in a child process, to publish its crashes (like a segfault) as job errors, enable with `true` or `1`. Each child process runs `MessageEvent::init_job_process`, which calls `MessageEvent::init` by default and delays every job by its duration (default: `false`) |
+//! | `WORKSPACE_ROOT`        | Directory where a temporary workspace is created for each job in `<WORKSPACE_ROOT>/<instance_id>/`, set in its `sdk_workspace` parameter. The workspaces of the instance are removed when it starts (default: disabled) |
+//! | `WORKSPACE_MAX_AGE`     | Age in seconds after which the workspaces left by the other instances in `WORKSPACE_ROOT`, like a crashed one, are removed when the worker starts. It must exceed the duration of the longest job, `0` disables it (default: `86400`) |
+//! | `WORKSPACE_KEEP_ON_FAILURE` | Keep the workspaces of the jobs published in error for debugging, not the retried ones, enable with `true` or `1` (default: `false`) |
+//! | `LOG_FORMAT`            | Format of the logs: `text` lines, or `json` lines with `timestamp`, `level`, `instance_id`, `queue`, `worker_name`, `worker_version`, `job_id`, `module`, `message` and the key/values of the record (default: `text`) |
+//! | `HTTP_PORT`             | Port of the embedded HTTP server exposing the Prometheus metrics on `/metrics`, the liveness probe on `/health/live` and the readiness probe on `/health/ready` (default: disabled) |
+//! | `JOB_TIMEOUT`           | Delay in milliseconds after which a job is aborted and published in error, overridden by the `sdk_timeout_ms` job parameter, `0` disables it. The job is cancelled and given 5 seconds to stop, otherwise the worker terminates with an error code to be restarted, unless `JOB_ISOLATION` is enabled (default: `0`) |
 //!
 //! ### Vault connection
 //!
@@ -61,21 +96,77 @@
 //! | `BACKEND_HOSTNAME` | URL used to connect to backend server (default: `http://127.0.0.1:4000/api`) |
 //! | `BACKEND_USERNAME` | Username used to connect to backend server |
 //! | `BACKEND_PASSWORD` | Password used to connect to backend server |
+//! | `BACKEND_CACHE_TTL` | Delay in seconds to keep the session token and the credential values, `0` disables the cache (default: `300`) |
+//! | `SECRETS_DIRECTORY` | Directory of the credentials of the `secret_file` store (default: `/run/secrets`) |
+//!
+//! The parameters with a `store` get their value from a credential store: `env` for environment variables,
+//! `secret_file` for mounted secrets, or a backend server configured with the `<STORE>_HOSTNAME`,
+//! `<STORE>_USERNAME`, `<STORE>_PASSWORD` and `<STORE>_CACHE_TTL` variables (like `BACKEND` above).
+//! Any other store code, including `file` and `secrets`, refers to such a backend server.
+//!
+//! ## Parameters templating
+//!
+//! The string values of the job parameters declared with `"template": true` can contain placeholders,
+//! resolved before processing the job: other parameters (`{work_dir}/{job_id}/out.mp4`),
+//! environment variables prefixed with `MCAI_TEMPLATE_` (`{env.MCAI_TEMPLATE_ROOT}`)
+//! and dates (`{date:%Y/%m/%d}`). A reference to an undefined parameter or variable is a `ParameterValueError`.
+//! See the [template](parameter/template/index.html) module.
+//!
+//! ## Job requirements
+//!
+//! A `requirements` parameter lists what the worker instance needs to process the job,
+//! otherwise the job is rejected with a `RequirementsError`:
+//!
+//! ```json
+//! {
+//!   "paths": ["/data/source.mp4"],
+//!   "disk_space": [{"path": "/data", "minimum": 10000000000}],
+//!   "memory": 2000000000,
+//!   "executables": ["ffmpeg"],
+//!   "environment_variables": ["AWS_REGION"],
+//!   "endpoints": ["database:5432"]
+//! }
+//! ```
+//!
+//! The `disk_space` paths may not exist yet, the disk of their nearest existing parent is checked.
+//! The `memory` is the available memory of the host, or the memory left before the cgroup limit
+//! of the container (`memory.max` with cgroup v2, `memory.limit_in_bytes` with cgroup v1) if it is lower.
+//!
+//! ## Health probes
+//!
+//! When `HTTP_PORT` is set, the embedded HTTP server answers `200` or `503` with JSON details:
+//!
+//! - `/health/live`: the worker is initializing, or its event loop is running (no heartbeat for 30 seconds means stalled),
+//! - `/health/ready`: `MessageEvent::init` succeeded, the worker is connected to the AMQP server,
+//!   consumes its queue (not paused), is not draining its jobs before terminating,
+//!   and no timed out job is still running after its grace period (`stalled`).
+//!
+//! The server starts before `MessageEvent::init`, so a long initialization is reported as not ready.
 //!
 //! ## Start worker locally
 //!
 //! MCAI Worker SDK can be launched locally - without RabbitMQ.
 //! It can process some message for different purpose (functional tests, message order examples, etc.).
 //!
-//! To start worker in this mode, setup the environment variable `SOURCE_ORDERS` with path(s) to json orders.
+//! To start worker in this mode, use the `run` subcommand,
+//! or setup the environment variable `SOURCE_ORDERS` with path(s) to json orders.
 //! It can take multiple orders, joined with `:` on unix platform, `;` on windows os.
+//! Directories (all their `.json` files) and glob patterns are also accepted.
+//!
+//! Each job result, and the job progressions, are written as JSON on the standard output,
+//! or in the directory set with `SOURCE_ORDERS_OUTPUT`.
+//! The worker exits with a non-zero code if any order failed.
 //!
 //! ### Examples:
 //!
 //! ```bash
 //! RUST_LOG=info SOURCE_ORDERS=./examples/success_order.json:./examples/error_order.json cargo run --example worker
+//! SOURCE_ORDERS="./examples/*_order.json" SOURCE_ORDERS_OUTPUT=./results cargo run --example worker
+//! cargo run --example worker -- run "./examples/*_order.json" --output ./results
 //! ```
 
+#[macro_use]
+extern crate lazy_static;
 #[macro_use]
 extern crate log;
 #[macro_use]
@@ -87,11 +178,18 @@ extern crate serde_json;
 extern crate yaserde_derive;
 
 mod channels;
+pub mod cli;
 mod config;
 mod error;
+mod health;
+mod http_server;
 pub mod job;
+mod logger;
 pub mod message;
+mod metrics;
 pub mod parameter;
+mod reconnection;
+pub mod source_orders;
 pub mod worker;
 
 /// Re-export from lapin Channel
@@ -101,7 +199,7 @@ pub use schemars::JsonSchema;
 /// Re-export from semver:
 pub use semver::Version;
 
-pub use error::{MessageError, Result};
+pub use error::{ErrorKind, JobError, MessageError, Result};
 #[cfg(feature = "media")]
 pub use message::media::{
   audio::AudioFormat,
@@ -112,27 +210,38 @@ pub use message::media::{
   video::{RegionOfInterest, Scaling, VideoFormat},
   StreamDescriptor,
 };
-pub use message::publish_job_progression;
+pub use message::{publish_job_progression, publish_job_progression_details, publish_job_status};
 pub use parameter::container::ParametersContainer;
 pub use parameter::{Parameter, ParameterValue, Requirement};
 #[cfg(feature = "media")]
 pub use stainless_ffmpeg::{format_context::FormatContext, frame::Frame};
 
-use crate::worker::docker;
-use chrono::prelude::*;
+use crate::cli::Command;
+use crate::worker::{control::WorkerControl, direct_messaging::DirectMessageHandler, docker};
 use config::*;
-use env_logger::Builder;
+use futures::channel::mpsc;
 use futures_executor::LocalPool;
-use futures_util::{future::FutureExt, stream::StreamExt, task::LocalSpawnExt};
+use futures_util::{
+  future::{select, Either, FutureExt},
+  pin_mut,
+  stream::StreamExt,
+  task::LocalSpawnExt,
+};
 use job::JobResult;
 use lapin::{options::*, types::FieldTable, Connection, ConnectionProperties};
 use serde::de::DeserializeOwned;
-#[cfg(feature = "media")]
 use serde::Serialize;
 use std::str::FromStr;
 #[cfg(feature = "media")]
 use std::sync::{mpsc::Sender, Mutex};
-use std::{cell::RefCell, fs, io::Write, rc::Rc, sync::Arc, thread, time};
+use std::{
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, RwLock,
+  },
+  thread, time,
+};
+use structopt::StructOpt;
 #[cfg(feature = "media")]
 use yaserde::YaSerialize;
 
@@ -208,6 +317,8 @@ impl ProcessFrame {
 
 /// # Trait to describe a worker
 /// Implement this trait to implement a worker
+///
+/// A panic in a callback is published as a processing error of the job, the worker keeps running.
 pub trait MessageEvent<P: DeserializeOwned + JsonSchema> {
   fn get_name(&self) -> String;
   fn get_short_description(&self) -> String;
@@ -218,6 +329,13 @@ pub trait MessageEvent<P: DeserializeOwned + JsonSchema> {
     Ok(())
   }
 
+  /// Called instead of `init` in the child process started for each job with `JOB_ISOLATION`
+  ///
+  /// A costly initialization, like loading a model, can be skipped here and done while processing the job.
+  fn init_job_process(&mut self) -> Result<()> {
+    self.init()
+  }
+
   #[cfg(feature = "media")]
   fn init_process(
     &mut self,
@@ -244,6 +362,9 @@ pub trait MessageEvent<P: DeserializeOwned + JsonSchema> {
   }
 
   /// Not called when the "media" feature is enabled
+  ///
+  /// A long processing should regularly check `job_result.is_cancelled()`,
+  /// to stop once the job is stopped or timed out.
   fn process(
     &self,
     _channel: Option<McaiChannel>,
@@ -258,29 +379,25 @@ pub trait MessageEvent<P: DeserializeOwned + JsonSchema> {
 }
 
 /// Function to start a worker
-pub fn start_worker<P: DeserializeOwned + JsonSchema, ME: MessageEvent<P>>(mut message_event: ME)
-where
-  ME: std::marker::Sync,
+///
+/// With a `WORKER_CONCURRENCY` greater than 1, jobs are processed in parallel threads
+/// sharing the same `MessageEvent` implementation, so `process` can be called concurrently.
+/// Media workers keep a job context between their callbacks, they always process one job at a time.
+pub fn start_worker<P: DeserializeOwned + JsonSchema + Send + 'static, ME: MessageEvent<P>>(
+  mut message_event: ME,
+) where
+  ME: std::marker::Send + std::marker::Sync + 'static,
 {
-  let mut builder = Builder::from_default_env();
+  let command = cli::Cli::from_args().get_command();
+  command.apply_environment();
+
   let amqp_queue = get_amqp_queue();
   let instance_id = docker::get_instance_id("/proc/self/cgroup");
-
-  let container_id = instance_id.clone();
-  builder
-    .format(move |stream, record| {
-      writeln!(
-        stream,
-        "{} - {} - {} - {} - {} - {}",
-        Utc::now(),
-        &container_id,
-        get_amqp_queue(),
-        record.target().parse::<i64>().unwrap_or(-1),
-        record.level(),
-        record.args(),
-      )
-    })
-    .init();
+  let log_level_handle = logger::init(
+    &instance_id,
+    &message_event.get_name(),
+    &message_event.get_version().to_string(),
+  );
 
   let worker_configuration =
     worker::WorkerConfiguration::new(&amqp_queue, &message_event, &instance_id);
@@ -298,89 +415,148 @@ where
     worker_configuration.get_sdk_version(),
   );
 
+  match &command {
+    Command::Describe(_) => {
+      print_json(&worker_configuration);
+      return;
+    }
+    Command::Schema => {
+      print_json(worker_configuration.get_parameters_schema());
+      return;
+    }
+    Command::Validate { order } => {
+      if let Err(error) = source_orders::validate::<P>(order) {
+        error!("Invalid order {:?}: {:?}", order, error);
+        std::process::exit(1);
+      }
+      info!("Order {:?} is valid", order);
+      return;
+    }
+    Command::Run(_) | Command::Consume(_) | Command::ProcessJob { .. } => {}
+  }
+
   if let Ok(enabled) = std::env::var("DESCRIBE") {
     if enabled == "1" || bool::from_str(&enabled.to_lowercase()).unwrap_or(false) {
-      match serde_json::to_string_pretty(&worker_configuration) {
-        Ok(serialized_configuration) => {
-          println!("{}", serialized_configuration);
-          return;
-        }
-        Err(error) => error!("Could not serialize worker configuration: {:?}", error),
-      }
+      print_json(&worker_configuration);
+      return;
     }
   }
 
-  if let Err(message) = message_event.init() {
+  // the probes answer during the initialization, the worker is not ready until it succeeds
+  let consumer = matches!(command, Command::Consume(_)) && get_source_orders().is_none();
+  if let (true, Some(http_port)) = (consumer, get_http_port()) {
+    match http_server::start(http_port) {
+      Ok(address) => info!("Metrics and health probes exposed on http://{}", address),
+      Err(error) => error!("Unable to start the HTTP server: {}", error),
+    }
+  }
+
+  let initialized = if let Command::ProcessJob { .. } = &command {
+    message_event.init_job_process()
+  } else {
+    message_event.init()
+  };
+  if let Err(message) = initialized {
     error!("{:?}", message);
     return;
   }
+  health::set_initialized();
 
-  let message_event_ref = Rc::new(RefCell::new(message_event));
+  let message_event_ref = Arc::new(RwLock::new(message_event));
 
-  info!("Worker initialized, ready to receive jobs");
+  if let Command::ProcessJob { workspace } = &command {
+    let workspace = workspace.as_ref().map(std::path::Path::new);
+    std::process::exit(message::process_child_job(message_event_ref, workspace));
+  }
 
-  if let Some(source_orders) = get_source_orders() {
-    warn!("Worker will process source orders");
-    for source_order in &source_orders {
-      info!("Start to process order: {:?}", source_order);
-
-      let count = None;
-      let channel = None;
-      let message_data = fs::read_to_string(source_order).unwrap();
-
-      let result = message::parse_and_process_message(
-        message_event_ref.clone(),
-        &message_data,
-        count,
-        channel,
-        message::publish_job_progression,
-      );
+  if let Some(workspaces_directory) = message::get_workspaces_directory() {
+    job::Workspace::sweep(&workspaces_directory);
 
-      match result {
-        Ok(mut job_result) => {
-          job_result.update_execution_duration();
-          info!(target: &job_result.get_job_id().to_string(), "Process succeeded: {:?}", job_result)
-        }
-        Err(message) => {
-          error!("{:?}", message);
-        }
-      }
+    let max_age = get_workspace_max_age();
+    if let (Some(root), true) = (workspaces_directory.parent(), max_age > 0) {
+      job::Workspace::sweep_other_instances(
+        root,
+        &workspaces_directory,
+        time::Duration::from_secs(max_age),
+      );
     }
+  }
+
+  info!("Worker initialized, ready to receive jobs");
 
+  let source_orders = match command {
+    Command::Run(options) => Some(options.orders),
+    _ => get_source_orders(),
+  };
+
+  if let Some(source_orders) = source_orders {
+    warn!("Worker will process source orders");
+    let local_output = source_orders::LocalOutput::new(get_source_orders_output());
+    if !source_orders::process(message_event_ref, &source_orders, local_output) {
+      error!("Some source orders failed");
+      std::process::exit(1);
+    }
     return;
   }
 
+  let concurrency = get_worker_concurrency();
+  info!("Worker processes up to {} job(s) concurrently", concurrency);
+  let (control_sender, mut control_receiver) = mpsc::unbounded::<WorkerControl>();
+  let processor_pool =
+    message::ProcessorPool::new(concurrency, message_event_ref, control_sender.clone());
+
+  worker::shutdown::listen_termination_signals(control_sender.clone());
+  let consuming = AtomicBool::new(true);
+  let mut reconnection = reconnection::ReconnectionBackoff::new(
+    time::Duration::from_millis(get_amqp_reconnection_delay()),
+    time::Duration::from_millis(get_amqp_reconnection_max_delay()),
+    get_amqp_reconnection_max_attempts(),
+  );
+
   loop {
+    health::heartbeat();
+    if apply_pending_controls(&mut control_receiver, &consuming) {
+      exit_worker();
+      return;
+    }
+
     let amqp_uri = get_amqp_uri();
     let mut executor = LocalPool::new();
     let spawner = executor.spawner();
 
-    executor.run_until(async {
-      let conn = Connection::connect_uri(
+    info!(
+      "Connect to AMQP server (attempt {})",
+      reconnection.get_attempts() + 1
+    );
+
+    spawner.spawn_local(health::beat()).unwrap();
+
+    let terminated = executor.run_until(async {
+      let conn = match Connection::connect_uri(
         amqp_uri,
         ConnectionProperties::default().with_default_executor(8),
       )
       .wait()
-      .unwrap();
+      {
+        Ok(conn) => conn,
+        Err(error) => {
+          error!("Unable to connect to AMQP server: {:?}", error);
+          return false;
+        }
+      };
 
       info!("Connected");
-      let channel = Arc::new(channels::declare_consumer_channel(
-        &conn,
-        &worker_configuration,
-      ));
+      health::set_connected(true);
 
-      let consumer = channel
-        .clone()
-        .basic_consume(
-          &amqp_queue,
-          "amqp_worker",
-          BasicConsumeOptions::default(),
-          FieldTable::default(),
-        )
-        .await
-        .unwrap();
+      let channel = match channels::declare_consumer_channel(&conn, &worker_configuration) {
+        Ok(channel) => Arc::new(channel),
+        Err(error) => {
+          error!("{:?}", error);
+          return false;
+        }
+      };
 
-      let status_consumer = channel
+      let status_consumer = match channel
         .clone()
         .basic_consume(
           &worker_configuration.get_direct_messaging_queue_name(),
@@ -389,45 +565,205 @@ where
           FieldTable::default(),
         )
         .await
+      {
+        Ok(status_consumer) => status_consumer,
+        Err(error) => {
+          error!("Unable to consume direct messages: {:?}", error);
+          return false;
+        }
+      };
+
+      let direct_message_handler = DirectMessageHandler::new(
+        channel.clone(),
+        worker_configuration.clone(),
+        control_sender.clone(),
+        log_level_handle.clone(),
+        processor_pool.get_in_flight_jobs(),
+      );
+
+      let mut status_consumption = spawner
+        .spawn_local_with_handle(async move {
+          let mut status_consumer = status_consumer;
+          while let Some(delivery) = status_consumer.next().await {
+            match delivery {
+              Ok((_channel, delivery)) => {
+                let _ = direct_message_handler.handle(delivery).await;
+              }
+              Err(error) => {
+                error!("Unable to consume direct messages: {:?}", error);
+                break;
+              }
+            }
+          }
+        })
         .unwrap();
 
-      let status_response_channel = channel.clone();
-      let status_worker_configuration = worker_configuration.clone();
+      let processor_pool = &processor_pool;
+
+      loop {
+        let control = if consuming.load(Ordering::SeqCst) {
+          let consumer = match channel
+            .clone()
+            .basic_consume(
+              &amqp_queue,
+              "amqp_worker",
+              BasicConsumeOptions::default(),
+              FieldTable::default(),
+            )
+            .await
+          {
+            Ok(consumer) => consumer,
+            Err(error) => {
+              error!("Unable to consume on queue {:?}: {:?}", amqp_queue, error);
+              return false;
+            }
+          };
+
+          info!("Start to consume on queue {:?}", amqp_queue);
+          health::set_consuming(true);
+          reconnection.reset();
+
+          let clone_channel = channel.clone();
+          let amqp_queue = &amqp_queue;
+          let consumption = async move {
+            let mut consumer = consumer;
+            while let Some(delivery) = consumer.next().await {
+              match delivery {
+                Ok((_channel, delivery)) => processor_pool.process(delivery, clone_channel.clone()),
+                Err(error) => {
+                  error!("Unable to consume on queue {:?}: {:?}", amqp_queue, error);
+                  break;
+                }
+              }
+            }
+          };
+          pin_mut!(consumption);
+
+          let control = loop {
+            match select(consumption.as_mut(), control_receiver.next()).await {
+              Either::Left(_) => return false,
+              Either::Right((Some(WorkerControl::ResumeConsuming), _)) => {}
+              Either::Right((control, _)) => break control,
+            }
+          };
+
+          info!("Stop to consume on queue {:?}", amqp_queue);
+          health::set_consuming(false);
+          if let Err(error) = channel
+            .basic_cancel("amqp_worker", BasicCancelOptions::default())
+            .await
+          {
+            error!("Unable to cancel the consumer: {:?}", error);
+          }
+          control
+        } else {
+          match select(&mut status_consumption, control_receiver.next()).await {
+            Either::Left(_) => return false,
+            Either::Right((control, _)) => control,
+          }
+        };
+
+        match control {
+          Some(WorkerControl::StopConsuming) => {
+            warn!("Worker paused, jobs are not consumed anymore");
+            consuming.store(false, Ordering::SeqCst);
+          }
+          Some(WorkerControl::ResumeConsuming) => {
+            info!("Worker resumed");
+            consuming.store(true, Ordering::SeqCst);
+          }
+          Some(WorkerControl::Terminate) | None => break,
+        }
+      }
 
-      let _consumer = spawner.spawn_local(async move {
-        status_consumer
-          .for_each(move |delivery| {
-            let (_channel, delivery) = delivery.expect("error caught in in consumer");
+      let grace_period = time::Duration::from_secs(get_shutdown_grace_period());
+      info!("Wait for running jobs (grace period: {:?})", grace_period);
+      health::set_draining();
+      if !processor_pool.drain(grace_period) {
+        processor_pool.requeue_in_flight_jobs();
+      }
 
-            worker::system_information::send_real_time_information(
-              delivery,
-              &status_response_channel,
-              &status_worker_configuration,
-            )
-            .map(|_| ())
-          })
-          .await
-      });
+      if let Err(error) = channel.close(200, "Worker terminated").await {
+        error!("Unable to close the channel: {:?}", error);
+      }
+      if let Err(error) = conn.close(200, "Worker terminated").await {
+        error!("Unable to close the connection: {:?}", error);
+      }
+      true
+    });
+    health::set_connected(false);
+    health::set_consuming(false);
 
-      info!("Start to consume on queue {:?}", amqp_queue);
+    if terminated {
+      exit_worker();
+      return;
+    }
 
-      let clone_channel = channel.clone();
-      let message_event = message_event_ref.clone();
+    let abandoned_jobs = processor_pool.abandon_in_flight_jobs();
+    if !abandoned_jobs.is_empty() {
+      warn!(
+        "Connection lost, {} in-flight job(s) abandoned, they will be delivered again",
+        abandoned_jobs.len()
+      );
+    }
 
-      consumer
-        .for_each(move |delivery| {
-          let (_channel, delivery) = delivery.expect("error caught in in consumer");
+    let delay = match reconnection.next_delay() {
+      Some(delay) => delay,
+      None => {
+        error!(
+          "Unable to connect to AMQP server after {} attempts, stop the worker",
+          reconnection.get_attempts()
+        );
+        return;
+      }
+    };
+
+    info!("Reconnection in {:?}...", delay);
+    metrics::reconnection();
+    let reconnection_instant = time::Instant::now() + delay;
+    while time::Instant::now() < reconnection_instant {
+      if apply_pending_controls(&mut control_receiver, &consuming) {
+        exit_worker();
+        return;
+      }
+      health::heartbeat();
+      thread::sleep(time::Duration::from_millis(100));
+    }
+  }
+}
 
-          message::process_message(message_event.clone(), delivery, clone_channel.clone())
-            .map(|_| ())
-        })
-        .await
-    });
+/// End of the worker, with an error code if a stalled job is still running, to restart it
+fn exit_worker() {
+  if health::is_stalled() {
+    error!("Worker terminated with a stalled job");
+    std::process::exit(1);
+  }
+  info!("Worker terminated");
+}
+
+/// Print the content as pretty JSON on the standard output
+fn print_json<T: Serialize>(content: &T) {
+  match serde_json::to_string_pretty(content) {
+    Ok(serialized_content) => println!("{}", serialized_content),
+    Err(error) => error!("Could not serialize {:?}", error),
+  }
+}
 
-    let sleep_duration = time::Duration::new(1, 0);
-    thread::sleep(sleep_duration);
-    info!("Reconnection...");
+/// Apply the control requests received while the worker is not connected
+///
+/// Returns `true` if the worker termination has been requested.
+fn apply_pending_controls(
+  control_receiver: &mut mpsc::UnboundedReceiver<WorkerControl>,
+  consuming: &AtomicBool,
+) -> bool {
+  while let Some(Some(control)) = control_receiver.next().now_or_never() {
+    match control {
+      WorkerControl::StopConsuming => consuming.store(false, Ordering::SeqCst),
+      WorkerControl::ResumeConsuming => consuming.store(true, Ordering::SeqCst),
+      WorkerControl::Terminate => return true,
+    }
   }
+  false
 }
 
 #[test]
@@ -0,0 +1,212 @@
+use crate::config::{get_amqp_queue, get_log_json};
+use chrono::prelude::*;
+use env_logger::{
+  filter::{Builder as FilterBuilder, Filter},
+  Builder, Logger,
+};
+use log::{
+  kv::{self, Key, VisitSource},
+  LevelFilter, Log, Metadata, Record,
+};
+use serde_json::{Map, Value};
+use std::{
+  io::Write,
+  sync::{Arc, RwLock},
+};
+
+/// Logger of the worker, filtered with `RUST_LOG` until the level is changed at runtime
+struct WorkerLogger {
+  filter: Arc<RwLock<Filter>>,
+  output: Logger,
+}
+
+impl Log for WorkerLogger {
+  fn enabled(&self, metadata: &Metadata) -> bool {
+    self
+      .filter
+      .read()
+      .map(|filter| filter.enabled(metadata))
+      .unwrap_or(false)
+  }
+
+  fn log(&self, record: &Record) {
+    let matches = self
+      .filter
+      .read()
+      .map(|filter| filter.matches(record))
+      .unwrap_or(false);
+
+    if matches {
+      self.output.log(record);
+    }
+  }
+
+  fn flush(&self) {
+    self.output.flush();
+  }
+}
+
+/// Handle to change the level of the worker logger
+#[derive(Clone)]
+pub struct LogLevelHandle {
+  filter: Arc<RwLock<Filter>>,
+}
+
+impl LogLevelHandle {
+  pub fn set_level(&self, level: LevelFilter) {
+    if let Ok(mut filter) = self.filter.write() {
+      *filter = FilterBuilder::new().filter_level(level).build();
+      log::set_max_level(level);
+    }
+  }
+}
+
+/// Context of the worker, added to each log line
+struct LogContext {
+  instance_id: String,
+  worker_name: String,
+  worker_version: String,
+}
+
+pub fn init(instance_id: &str, worker_name: &str, worker_version: &str) -> LogLevelHandle {
+  let filter = FilterBuilder::from_env("RUST_LOG").build();
+  let max_level = filter.filter();
+  let filter = Arc::new(RwLock::new(filter));
+
+  let context = LogContext {
+    instance_id: instance_id.to_string(),
+    worker_name: worker_name.to_string(),
+    worker_version: worker_version.to_string(),
+  };
+  let json = get_log_json();
+
+  let output = Builder::new()
+    .filter_level(LevelFilter::Trace)
+    .format(move |stream, record| {
+      if json {
+        writeln!(stream, "{}", format_json(record, &context))
+      } else {
+        writeln!(
+          stream,
+          "{} - {} - {} - {} - {} - {}",
+          Utc::now(),
+          &context.instance_id,
+          get_amqp_queue(),
+          get_job_id(record).unwrap_or(-1),
+          record.level(),
+          record.args(),
+        )
+      }
+    })
+    .build();
+
+  let logger = WorkerLogger {
+    filter: filter.clone(),
+    output,
+  };
+
+  if log::set_boxed_logger(Box::new(logger)).is_ok() {
+    log::set_max_level(max_level);
+  }
+
+  LogLevelHandle { filter }
+}
+
+/// Job identifier of the record, given with a `job_id` key or as the target of the record
+fn get_job_id(record: &Record) -> Option<i64> {
+  record
+    .key_values()
+    .get(Key::from_str("job_id"))
+    .and_then(|value| value.to_i64())
+    .or_else(|| record.target().parse::<i64>().ok())
+}
+
+fn format_json(record: &Record, context: &LogContext) -> Value {
+  let mut fields = KeyValues(Map::new());
+  // the key/values can not fail to be visited into the map
+  let _ = record.key_values().visit(&mut fields);
+  let mut fields = fields.0;
+
+  fields.insert("timestamp".to_string(), json!(Utc::now().to_rfc3339()));
+  fields.insert("level".to_string(), json!(record.level().to_string()));
+  fields.insert("instance_id".to_string(), json!(context.instance_id));
+  fields.insert("queue".to_string(), json!(get_amqp_queue()));
+  fields.insert("worker_name".to_string(), json!(context.worker_name));
+  fields.insert("worker_version".to_string(), json!(context.worker_version));
+  fields.insert("job_id".to_string(), json!(get_job_id(record)));
+  fields.insert(
+    "module".to_string(),
+    json!(record.module_path().unwrap_or_else(|| record.target())),
+  );
+  fields.insert("message".to_string(), json!(record.args().to_string()));
+
+  Value::Object(fields)
+}
+
+/// Collect the key/values of a record as JSON values
+struct KeyValues(Map<String, Value>);
+
+impl<'kvs> VisitSource<'kvs> for KeyValues {
+  fn visit_pair(&mut self, key: Key<'kvs>, value: kv::Value<'kvs>) -> Result<(), kv::Error> {
+    let value = if let Some(value) = value.to_bool() {
+      json!(value)
+    } else if let Some(value) = value.to_i64() {
+      json!(value)
+    } else if let Some(value) = value.to_u64() {
+      json!(value)
+    } else if let Some(value) = value.to_f64() {
+      json!(value)
+    } else {
+      json!(value.to_string())
+    };
+
+    self.0.insert(key.to_string(), value);
+    Ok(())
+  }
+}
+
+#[test]
+fn json_log_format() {
+  let context = LogContext {
+    instance_id: "0123456789ab".to_string(),
+    worker_name: "sample_worker".to_string(),
+    worker_version: "1.2.3".to_string(),
+  };
+
+  let key_values = [
+    ("job_id", kv::Value::from(123)),
+    ("path", kv::Value::from("/data/file.mp4")),
+  ];
+  // the arguments of the record only live until the end of the statement
+  let line = format_json(
+    &Record::builder()
+      .args(format_args!("Process {}", "started"))
+      .level(log::Level::Info)
+      .target("mcai_worker_sdk::message")
+      .module_path(Some("mcai_worker_sdk::message"))
+      .key_values(&key_values)
+      .build(),
+    &context,
+  );
+  assert_eq!(line["level"], json!("INFO"));
+  assert_eq!(line["instance_id"], json!("0123456789ab"));
+  assert_eq!(line["queue"], json!("job_undefined"));
+  assert_eq!(line["worker_name"], json!("sample_worker"));
+  assert_eq!(line["worker_version"], json!("1.2.3"));
+  assert_eq!(line["job_id"], json!(123));
+  assert_eq!(line["module"], json!("mcai_worker_sdk::message"));
+  assert_eq!(line["message"], json!("Process started"));
+  assert_eq!(line["path"], json!("/data/file.mp4"));
+  assert!(DateTime::parse_from_rfc3339(line["timestamp"].as_str().unwrap()).is_ok());
+
+  let line = format_json(
+    &Record::builder()
+      .args(format_args!("Completed"))
+      .level(log::Level::Warn)
+      .target("456")
+      .build(),
+    &context,
+  );
+  assert_eq!(line["job_id"], json!(456));
+  assert_eq!(line["module"], json!("456"));
+}
@@ -0,0 +1,98 @@
+//! Threads processing a job that the worker already gave up, like a requeued or a timed out job
+//!
+//! Their job is cancelled, but they may still run for a while: their progressions,
+//! status updates and results must not be published anymore.
+
+use std::{
+  collections::{HashMap, HashSet},
+  sync::Mutex,
+  thread::{self, ThreadId},
+};
+
+lazy_static! {
+  static ref ABANDONED_THREADS: Mutex<HashSet<ThreadId>> = Mutex::new(HashSet::new());
+  /// Threads processing the job of another thread, abandoned along with it
+  static ref JOB_THREADS: Mutex<HashMap<ThreadId, ThreadId>> = Mutex::new(HashMap::new());
+}
+
+/// Abandon the job of a thread, and the threads processing it
+pub(crate) fn abandon(thread_id: ThreadId) {
+  let job_threads: Vec<ThreadId> = JOB_THREADS
+    .lock()
+    .map(|job_threads| {
+      job_threads
+        .iter()
+        .filter(|(_, parent_id)| **parent_id == thread_id)
+        .map(|(job_thread_id, _)| *job_thread_id)
+        .collect()
+    })
+    .unwrap_or_default();
+
+  if let Ok(mut threads) = ABANDONED_THREADS.lock() {
+    threads.insert(thread_id);
+    threads.extend(job_threads);
+  }
+}
+
+/// Register a thread processing the job of the current thread
+pub(crate) fn attach(job_thread_id: ThreadId) {
+  if let Ok(mut job_threads) = JOB_THREADS.lock() {
+    job_threads.insert(job_thread_id, thread::current().id());
+  }
+}
+
+/// Whether the current thread processes an abandoned job
+pub(crate) fn is_abandoned() -> bool {
+  ABANDONED_THREADS
+    .lock()
+    .map(|threads| threads.contains(&thread::current().id()))
+    .unwrap_or(false)
+}
+
+/// The current thread is done with its abandoned job, and can process other ones
+pub(crate) fn release() {
+  let thread_id = thread::current().id();
+  if let Ok(mut threads) = ABANDONED_THREADS.lock() {
+    threads.remove(&thread_id);
+  }
+  if let Ok(mut job_threads) = JOB_THREADS.lock() {
+    job_threads
+      .retain(|job_thread_id, parent_id| *job_thread_id != thread_id && *parent_id != thread_id);
+  }
+}
+
+#[test]
+fn abandoned_threads() {
+  let thread_id = thread::current().id();
+  assert!(!is_abandoned());
+
+  abandon(thread_id);
+  assert!(is_abandoned());
+  assert!(!thread::spawn(is_abandoned).join().unwrap());
+
+  release();
+  assert!(!is_abandoned());
+}
+
+#[test]
+fn abandoned_job_threads() {
+  use std::sync::mpsc;
+
+  let (sender, receiver) = mpsc::channel::<()>();
+  let job_thread = thread::spawn(move || {
+    receiver.recv().unwrap();
+    let abandoned = is_abandoned();
+    release();
+    abandoned
+  });
+  let job_thread_id = job_thread.thread().id();
+  attach(job_thread_id);
+
+  abandon(thread::current().id());
+  sender.send(()).unwrap();
+  assert!(job_thread.join().unwrap());
+
+  release();
+  assert!(!is_abandoned());
+  assert!(!JOB_THREADS.lock().unwrap().contains_key(&job_thread_id));
+}
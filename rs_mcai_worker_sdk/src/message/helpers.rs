@@ -1,27 +1,113 @@
+use crate::job::Job;
 use amq_protocol_types::{AMQPValue, FieldTable};
-use lapin::message::Delivery;
+use lapin::{message::Delivery, BasicProperties};
 
-pub fn get_message_death_count(message: &Delivery) -> Option<i64> {
-  get_count_from_header(message.properties.headers())
+/// Header counting the retries of a job, set by the worker when it republishes the job message
+///
+/// The `x-death` header is only added by the broker when it dead-letters a message,
+/// RabbitMQ ignores the one of a published message like a republished retry.
+static RETRY_COUNT_HEADER: &str = "x-mcai-retry-count";
+
+/// Record of a message dead-lettering, read from the `x-death` header
+#[derive(Debug, PartialEq, Serialize)]
+pub struct DeathRecord {
+  pub queue: Option<String>,
+  pub reason: Option<String>,
+  pub count: i64,
+  pub time: Option<u64>,
 }
 
-fn get_count_from_header(header: &Option<FieldTable>) -> Option<i64> {
-  if let Some(header) = header {
-    if let Some(death) = header.inner().get("x-death") {
-      if let AMQPValue::FieldArray(array) = death {
-        let raw_array = array.as_slice();
-        if raw_array.is_empty() {
-          return None;
-        }
-        if let AMQPValue::FieldTable(params) = &raw_array[0] {
-          if let Some(AMQPValue::LongLongInt(value)) = params.inner().get("count") {
-            return Some(*value);
-          }
-        }
-      }
-    }
+pub fn get_message_job_id(message: &Delivery) -> Option<u64> {
+  std::str::from_utf8(&message.data)
+    .ok()
+    .and_then(|data| Job::new(data).ok())
+    .map(|job| job.job_id)
+}
+
+pub fn get_message_retry_count(message: &Delivery) -> Option<i64> {
+  get_retry_count_from_header(message.properties.headers())
+}
+
+/// Properties of a job message republished for its next retry
+pub fn with_retry_count(properties: &BasicProperties, count: i64) -> BasicProperties {
+  let mut headers = properties.headers().clone().unwrap_or_default();
+  headers.insert(RETRY_COUNT_HEADER.into(), AMQPValue::LongLongInt(count));
+  properties.clone().with_headers(headers)
+}
+
+pub fn get_message_death_history(message: &Delivery) -> Vec<DeathRecord> {
+  get_history_from_header(message.properties.headers())
+}
+
+fn get_history_from_header(header: &Option<FieldTable>) -> Vec<DeathRecord> {
+  let deaths = header
+    .as_ref()
+    .and_then(|header| header.inner().get("x-death"));
+
+  let array = match deaths {
+    Some(AMQPValue::FieldArray(array)) => array.as_slice(),
+    _ => return vec![],
   };
-  None
+
+  array
+    .iter()
+    .filter_map(|death| match death {
+      AMQPValue::FieldTable(params) => {
+        let params = params.inner();
+        let get_string = |key: &str| match params.get(key) {
+          Some(AMQPValue::LongString(value)) => Some(value.as_str().to_string()),
+          Some(AMQPValue::ShortString(value)) => Some(value.as_str().to_string()),
+          _ => None,
+        };
+
+        Some(DeathRecord {
+          queue: get_string("queue"),
+          reason: get_string("reason"),
+          count: match params.get("count") {
+            Some(AMQPValue::LongLongInt(value)) => *value,
+            _ => 0,
+          },
+          time: match params.get("time") {
+            Some(AMQPValue::Timestamp(value)) => Some(*value),
+            _ => None,
+          },
+        })
+      }
+      _ => None,
+    })
+    .collect()
+}
+
+/// Count the retries of a message, from the header set by the worker
+///
+/// The `x-death` header is only used for the messages retried without it, by a previous version.
+fn get_retry_count_from_header(header: &Option<FieldTable>) -> Option<i64> {
+  let retry_count = header
+    .as_ref()
+    .and_then(|header| header.inner().get(RETRY_COUNT_HEADER));
+
+  match retry_count {
+    Some(AMQPValue::LongLongInt(count)) => Some(*count),
+    _ => get_count_from_header(header),
+  }
+}
+
+/// Count the retries of a message from its dead-letterings
+///
+/// Each retry expires from a delayed queue, the rejections from the job queue are not counted twice.
+fn get_count_from_header(header: &Option<FieldTable>) -> Option<i64> {
+  let history = get_history_from_header(header);
+  if history.is_empty() {
+    return None;
+  }
+
+  Some(
+    history
+      .iter()
+      .filter(|record| record.reason.as_deref() != Some("rejected"))
+      .map(|record| record.count)
+      .sum(),
+  )
 }
 
 #[test]
@@ -53,3 +139,107 @@ fn header_information() {
   let count = get_count_from_header(&header);
   assert!(count == Some(666));
 }
+
+#[test]
+fn header_history() {
+  use std::collections::BTreeMap;
+
+  assert!(get_history_from_header(&None).is_empty());
+
+  let mut properties = FieldTable::from(BTreeMap::new());
+  properties.insert("count".into(), AMQPValue::LongLongInt(3));
+  properties.insert(
+    "queue".into(),
+    AMQPValue::LongString("job_worker".to_string().into()),
+  );
+  properties.insert(
+    "reason".into(),
+    AMQPValue::LongString("rejected".to_string().into()),
+  );
+  properties.insert("time".into(), AMQPValue::Timestamp(1_600_000_000));
+
+  let mut map = FieldTable::from(BTreeMap::new());
+  map.insert(
+    "x-death".into(),
+    AMQPValue::FieldArray(vec![AMQPValue::FieldTable(properties)].into()),
+  );
+
+  let history = get_history_from_header(&Some(map));
+  assert_eq!(
+    history,
+    vec![DeathRecord {
+      queue: Some("job_worker".to_string()),
+      reason: Some("rejected".to_string()),
+      count: 3,
+      time: Some(1_600_000_000),
+    }]
+  );
+}
+
+#[test]
+fn header_retries_count() {
+  use std::collections::BTreeMap;
+
+  let death_record = |queue: &str, reason: &str, count: i64| {
+    let mut properties = FieldTable::from(BTreeMap::new());
+    properties.insert("count".into(), AMQPValue::LongLongInt(count));
+    properties.insert(
+      "queue".into(),
+      AMQPValue::LongString(queue.to_string().into()),
+    );
+    properties.insert(
+      "reason".into(),
+      AMQPValue::LongString(reason.to_string().into()),
+    );
+    AMQPValue::FieldTable(properties)
+  };
+
+  let mut map = FieldTable::from(BTreeMap::new());
+  map.insert(
+    "x-death".into(),
+    AMQPValue::FieldArray(
+      vec![
+        death_record("job_delayed_30000", "expired", 1),
+        death_record("job_delayed_5000", "expired", 1),
+        death_record("job_delayed", "expired", 2),
+        death_record("job_worker", "rejected", 2),
+      ]
+      .into(),
+    ),
+  );
+  let count = get_count_from_header(&Some(map));
+  assert_eq!(count, Some(4));
+}
+
+#[test]
+fn header_republished_retries_count() {
+  use std::collections::BTreeMap;
+
+  // the broker dead-lettered the message once, it does not update the header of a republished one
+  let mut properties = FieldTable::from(BTreeMap::new());
+  properties.insert("count".into(), AMQPValue::LongLongInt(1));
+  properties.insert(
+    "reason".into(),
+    AMQPValue::LongString("expired".to_string().into()),
+  );
+  let mut map = FieldTable::from(BTreeMap::new());
+  map.insert(
+    "x-death".into(),
+    AMQPValue::FieldArray(vec![AMQPValue::FieldTable(properties)].into()),
+  );
+  let properties = BasicProperties::default().with_headers(map);
+  assert_eq!(get_retry_count_from_header(properties.headers()), Some(1));
+
+  let republished = with_retry_count(&properties, 2);
+  assert_eq!(get_retry_count_from_header(republished.headers()), Some(2));
+
+  let republished = with_retry_count(&republished, 3);
+  assert_eq!(get_retry_count_from_header(republished.headers()), Some(3));
+  assert_eq!(
+    get_history_from_header(republished.headers()),
+    get_history_from_header(properties.headers())
+  );
+
+  let properties = with_retry_count(&BasicProperties::default(), 1);
+  assert_eq!(get_retry_count_from_header(properties.headers()), Some(1));
+}
@@ -0,0 +1,397 @@
+//! Process each job in a child process of the worker
+//!
+//! The child process runs the same executable with the hidden `process-job` subcommand.
+//! It reads the job message on its standard input, and writes its progressions, status updates
+//! and result as JSON lines on its standard output, prefixed to be told apart from the output
+//! of the worker code. A crash of the child process (like a segfault in a C library) is published
+//! as a job error, while the worker keeps consuming.
+//!
+//! As a new process is started for each job, `MessageEvent::init_job_process` is called before
+//! each job. It calls `MessageEvent::init` by default: a worker with a costly initialization,
+//! like loading a model, can override it to skip the initialization or to do it lazily.
+
+use super::{
+  flush_job_progression, parse_and_process_message, publish_job_progression,
+  publish_job_progression_details, publish_job_status, TIMEOUT_GRACE_PERIOD,
+};
+use crate::{
+  job::{CancellationToken, Job, JobProgression, JobResult, JobStatus, JobStatusUpdate},
+  JobError, McaiChannel, MessageError, MessageEvent, Result,
+};
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use std::{
+  collections::VecDeque,
+  env,
+  io::{self, BufRead, BufReader, Read, Write},
+  path::Path,
+  process::{Child, ChildStderr, Command, ExitStatus, Stdio},
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex, RwLock,
+  },
+  thread,
+  time::{Duration, Instant},
+};
+
+/// Hidden subcommand of the worker to process a job in a child process
+pub static CHILD_PROCESS_COMMAND: &str = "process-job";
+/// Number of lines of the child process standard error kept to report a crash
+static STDERR_TAIL_LINES: usize = 20;
+/// Prefix of the messages sent by the child process, the rest of its output is logged
+static MESSAGE_PREFIX: &str = "\u{1e}mcai_job_message:";
+
+static CHILD_PROCESS: AtomicBool = AtomicBool::new(false);
+
+/// Messages sent by the child process to the worker
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", content = "content", rename_all = "snake_case")]
+enum ChildMessage {
+  Progression(JobProgression),
+  Status(JobStatusUpdate),
+  Result(std::result::Result<JobResult, MessageError>),
+}
+
+/// Whether the current process is a child process processing a job
+pub(crate) fn is_child_process() -> bool {
+  CHILD_PROCESS.load(Ordering::SeqCst)
+}
+
+pub(crate) fn send_progression(job_progression: JobProgression) {
+  send(&ChildMessage::Progression(job_progression));
+}
+
+pub(crate) fn send_status(job_status_update: JobStatusUpdate) {
+  send(&ChildMessage::Status(job_status_update));
+}
+
+fn send(message: &ChildMessage) {
+  let content = json!(message).to_string();
+  let stdout = io::stdout();
+  let mut stdout = stdout.lock();
+  // starts a new line, in case the worker code printed a partial one
+  let sent = writeln!(stdout, "\n{}{}", MESSAGE_PREFIX, content).and_then(|_| stdout.flush());
+  if let Err(error) = sent {
+    error!("Unable to send message to the worker: {}", error);
+  }
+}
+
+/// Process the job read on the standard input, returns the exit code of the child process
+pub fn process_child_job<P, ME>(message_event: Arc<RwLock<ME>>, workspace: Option<&Path>) -> i32
+where
+  P: DeserializeOwned + JsonSchema + Send + 'static,
+  ME: MessageEvent<P> + Send + Sync + 'static,
+{
+  CHILD_PROCESS.store(true, Ordering::SeqCst);
+
+  let mut message_data = String::new();
+  if let Err(error) = io::stdin().read_to_string(&mut message_data) {
+    error!("Unable to read the job message: {}", error);
+    return 1;
+  }
+
+  let result = parse_and_process_message(
+    message_event,
+    &message_data,
+    None,
+    None,
+    publish_job_progression,
+    CancellationToken::default(),
+    workspace,
+  );
+
+  send(&ChildMessage::Result(result));
+  0
+}
+
+/// Process the job in a child process, killed if the job is cancelled
+///
+/// The child process handles the timeout of the job, it is killed if it still runs
+/// after the timeout and the grace period given to the job to stop.
+pub(crate) fn process_in_child_process(
+  message_data: &str,
+  channel: Option<McaiChannel>,
+  cancellation_token: &CancellationToken,
+  workspace: Option<&Path>,
+) -> Result<JobResult> {
+  let executable = env::current_exe().map_err(|error| {
+    MessageError::RuntimeError(format!("Unable to find the worker executable: {}", error))
+  })?;
+
+  let mut command = Command::new(executable);
+  command.arg(CHILD_PROCESS_COMMAND);
+  if let Some(workspace) = workspace {
+    command.arg("--workspace").arg(workspace);
+  }
+
+  let mut child = command
+    .stdin(Stdio::piped())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .spawn()
+    .map_err(|error| {
+      MessageError::RuntimeError(format!("Unable to start the job process: {}", error))
+    })?;
+
+  let pipes = (child.stdin.take(), child.stdout.take(), child.stderr.take());
+  let (mut stdin, stdout, stderr) = match pipes {
+    (Some(stdin), Some(stdout), Some(stderr)) => (stdin, stdout, stderr),
+    _ => {
+      let _ = child.kill();
+      return Err(MessageError::RuntimeError(
+        "Unable to communicate with the job process".to_string(),
+      ));
+    }
+  };
+
+  if let Err(error) = stdin.write_all(message_data.as_bytes()) {
+    warn!(
+      "Unable to send the job message to the job process: {}",
+      error
+    );
+  }
+  drop(stdin);
+
+  let job = Job::new(message_data).ok();
+  let deadline = job
+    .as_ref()
+    .and_then(Job::get_timeout)
+    .map(|timeout| Instant::now() + timeout + TIMEOUT_GRACE_PERIOD * 2);
+
+  let stderr_reader = thread::spawn(move || read_stderr_tail(stderr));
+  let child = Arc::new(Mutex::new(child));
+  let finished = Arc::new(AtomicBool::new(false));
+  watch_cancellation(
+    child.clone(),
+    finished.clone(),
+    cancellation_token.clone(),
+    deadline,
+  );
+
+  let mut result = None;
+  let mut last_status = None;
+  for line in BufReader::new(stdout).lines() {
+    let line = match line {
+      Ok(line) => line,
+      Err(error) => {
+        warn!("Unable to read the job process output: {}", error);
+        break;
+      }
+    };
+
+    match parse_message(&line) {
+      Ok(ChildMessage::Progression(job_progression)) => {
+        if let Err(error) = publish_job_progression_details(channel.clone(), job_progression) {
+          error!("Unable to publish job progression: {:?}", error);
+        }
+      }
+      Ok(ChildMessage::Status(job_status_update)) => {
+        last_status = Some(job_status_update.get_status().clone());
+        publish_job_status(channel.clone(), job_status_update);
+      }
+      Ok(ChildMessage::Result(job_result)) => result = Some(job_result),
+      Err(_) => {
+        if !line.is_empty() {
+          debug!("Job process output: {}", line);
+        }
+      }
+    }
+  }
+
+  finished.store(true, Ordering::SeqCst);
+  let status = child
+    .lock()
+    .map_err(|error| {
+      MessageError::RuntimeError(format!("Unable to access job process: {}", error))
+    })?
+    .wait()
+    .map_err(|error| {
+      MessageError::RuntimeError(format!("Unable to wait for the job process: {}", error))
+    })?;
+  let stderr_tail = stderr_reader.join().unwrap_or_default();
+
+  let job_id = job.map(|job| job.job_id);
+  if let Some(job_id) = job_id {
+    flush_job_progression(job_id);
+  }
+
+  if let Some(result) = result {
+    return result;
+  }
+
+  let error = get_crash_error(&status, stderr_tail);
+  error!("{}", error.get_message());
+
+  if let (Some(job_id), Some(last_status)) = (job_id, last_status) {
+    let status = if cancellation_token.is_cancelled() {
+      JobStatus::Stopped
+    } else {
+      JobStatus::Error
+    };
+    publish_job_status(
+      channel,
+      JobStatusUpdate::new(job_id, Some(last_status), status),
+    );
+  }
+
+  Err(error.into())
+}
+
+/// Parse a message of the child process, the output of the worker code before its prefix is ignored
+fn parse_message(line: &str) -> std::result::Result<ChildMessage, String> {
+  let position = line
+    .find(MESSAGE_PREFIX)
+    .ok_or_else(|| "Not a message of the job process".to_string())?;
+
+  serde_json::from_str(&line[position + MESSAGE_PREFIX.len()..]).map_err(|error| error.to_string())
+}
+
+/// Kill the child process once the job is cancelled, or once the deadline is passed
+fn watch_cancellation(
+  child: Arc<Mutex<Child>>,
+  finished: Arc<AtomicBool>,
+  cancellation_token: CancellationToken,
+  deadline: Option<Instant>,
+) {
+  thread::spawn(move || {
+    while !finished.load(Ordering::SeqCst) {
+      let expired = matches!(deadline, Some(deadline) if Instant::now() >= deadline);
+      if expired {
+        warn!("Job process still running after the job timeout, it is killed");
+      }
+
+      if cancellation_token.is_cancelled() || expired {
+        if let Ok(mut child) = child.lock() {
+          if let Err(error) = child.kill() {
+            warn!("Unable to kill the job process: {}", error);
+          }
+        }
+        return;
+      }
+      thread::sleep(Duration::from_millis(100));
+    }
+  });
+}
+
+/// Forward the standard error of the child process, and keep its last lines
+fn read_stderr_tail(stderr: ChildStderr) -> Vec<String> {
+  let mut tail = VecDeque::with_capacity(STDERR_TAIL_LINES);
+
+  for line in BufReader::new(stderr).lines() {
+    let line = match line {
+      Ok(line) => line,
+      Err(_) => break,
+    };
+
+    eprintln!("{}", line);
+    if tail.len() == STDERR_TAIL_LINES {
+      tail.pop_front();
+    }
+    tail.push_back(line);
+  }
+
+  tail.into_iter().collect()
+}
+
+fn get_crash_error(status: &ExitStatus, stderr_tail: Vec<String>) -> JobError {
+  let signal = get_signal(status);
+
+  let reason = match (status.code(), signal) {
+    (_, Some(signal)) => format!("killed by signal {}", signal),
+    (Some(code), None) => format!("exited with code {} without result", code),
+    (None, None) => "terminated without result".to_string(),
+  };
+
+  JobError::permanent(&format!("Job process {}", reason))
+    .with_code("crash")
+    .with_details(&json!({
+      "exit_code": status.code(),
+      "signal": signal,
+      "stderr": stderr_tail,
+    }))
+}
+
+#[cfg(unix)]
+fn get_signal(status: &ExitStatus) -> Option<i32> {
+  use std::os::unix::process::ExitStatusExt;
+  status.signal()
+}
+
+#[cfg(not(unix))]
+fn get_signal(_status: &ExitStatus) -> Option<i32> {
+  None
+}
+
+#[test]
+fn child_messages() {
+  let messages = vec![
+    ChildMessage::Progression(JobProgression::new(123, 50)),
+    ChildMessage::Status(JobStatusUpdate::new(123, None, JobStatus::Initializing)),
+    ChildMessage::Result(Ok(JobResult::new(123).with_status(JobStatus::Completed))),
+    ChildMessage::Result(Err(MessageError::ProcessingError(
+      JobResult::new(123)
+        .with_status(JobStatus::Error)
+        .with_message("failed"),
+    ))),
+    ChildMessage::Result(Err(MessageError::NotImplemented())),
+    ChildMessage::Result(Err(MessageError::JobError(
+      JobError::transient("unavailable").with_code("busy"),
+    ))),
+  ];
+
+  for message in messages {
+    let line = format!("partial output{}{}", MESSAGE_PREFIX, json!(message));
+    let parsed = parse_message(&line).unwrap();
+
+    match (message, parsed) {
+      (ChildMessage::Progression(expected), ChildMessage::Progression(parsed)) => {
+        assert_eq!(parsed.get_job_id(), expected.get_job_id());
+        assert_eq!(parsed.get_progression(), expected.get_progression());
+      }
+      (ChildMessage::Status(expected), ChildMessage::Status(parsed)) => {
+        assert_eq!(parsed.get_status(), expected.get_status());
+      }
+      (ChildMessage::Result(Ok(expected)), ChildMessage::Result(Ok(parsed))) => {
+        assert_eq!(parsed.get_status(), expected.get_status());
+      }
+      (ChildMessage::Result(Err(expected)), ChildMessage::Result(Err(parsed))) => {
+        assert_eq!(
+          format!("{:?}", parsed.get_kind()),
+          format!("{:?}", expected.get_kind())
+        );
+      }
+      (expected, parsed) => panic!("{:?} parsed as {:?}", expected, parsed),
+    }
+  }
+
+  let result = json!(ChildMessage::Result(Err(MessageError::NotImplemented())));
+  assert!(parse_message(&result.to_string()).is_err());
+  assert!(parse_message("{\"type\": \"status\"}").is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn child_process_crash() {
+  use std::os::unix::process::ExitStatusExt;
+
+  let error = get_crash_error(
+    &ExitStatus::from_raw(11),
+    vec!["Segmentation fault".to_string()],
+  );
+  assert_eq!(error.get_message(), "Job process killed by signal 11");
+  assert_eq!(error.get_code(), Some(&"crash".to_string()));
+  assert_eq!(
+    error.get_details(),
+    Some(&json!({
+      "exit_code": null,
+      "signal": 11,
+      "stderr": ["Segmentation fault"],
+    }))
+  );
+
+  let error = get_crash_error(&ExitStatus::from_raw(2 << 8), vec![]);
+  assert_eq!(
+    error.get_message(),
+    "Job process exited with code 2 without result"
+  );
+}
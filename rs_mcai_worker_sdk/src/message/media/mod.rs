@@ -1,15 +1,15 @@
 use crate::{
   job::{Job, JobResult, JobStatus},
-  message::publish_job_progression,
+  message::{catch_panic, publish_job_progression},
+  metrics,
   parameter::container::ParametersContainer,
-  AudioFilter, McaiChannel, MessageEvent, Result,
+  AudioFilter, McaiChannel, MessageError, MessageEvent, Result,
 };
 use filters::VideoFilter;
 use schemars::JsonSchema;
 use serde::de::DeserializeOwned;
 use source::DecodeResult;
-use std::cell::RefCell;
-use std::rc::Rc;
+use std::sync::{Arc, RwLock};
 
 pub mod audio;
 pub mod ebu_ttml_live;
@@ -73,16 +73,17 @@ pub struct ImageConfiguration {
 }
 
 pub fn process<P: DeserializeOwned + JsonSchema, ME: MessageEvent<P>>(
-  message_event: Rc<RefCell<ME>>,
+  message_event: Arc<RwLock<ME>>,
   channel: Option<McaiChannel>,
   job: &Job,
   parameters: P,
   job_result: JobResult,
 ) -> Result<JobResult> {
   let str_job_id = job.job_id.to_string();
+  metrics::media_job_started();
 
-  let source_url: String = job.get_parameter(SOURCE_PATH_PARAMETER)?;
-  let output_url: String = job.get_parameter(DESTINATION_PATH_PARAMETER)?;
+  let source_url: String = job.get_templated_parameter(SOURCE_PATH_PARAMETER)?;
+  let output_url: String = job.get_templated_parameter(DESTINATION_PATH_PARAMETER)?;
   let start_index_ms: Option<i64> = job.get_parameter(START_INDEX_PARAMETER).ok();
   let stop_index_ms: Option<i64> = job.get_parameter(STOP_INDEX_PARAMETER).ok();
 
@@ -113,6 +114,18 @@ pub fn process<P: DeserializeOwned + JsonSchema, ME: MessageEvent<P>>(
   let mut previous_progress = 0;
 
   loop {
+    if job_result.is_cancelled() {
+      info!(target: &str_job_id, "Process cancelled");
+      let mut worker = message_event.write().map_err(|error| {
+        MessageError::RuntimeError(format!("Unable to access worker: {}", error))
+      })?;
+      catch_panic(job.job_id, || worker.ending_process())?;
+      drop(worker);
+
+      output.complete()?;
+      return Ok(job_result.with_status(JobStatus::Stopped));
+    }
+
     match source.next_frame()? {
       DecodeResult::Frame {
         stream_index,
@@ -131,17 +144,25 @@ pub fn process<P: DeserializeOwned + JsonSchema, ME: MessageEvent<P>>(
         }
 
         trace!(target: &job_result.get_str_job_id(), "Process frame {}", count);
-        let result =
-          message_event
-            .borrow_mut()
-            .process_frame(job_result.clone(), stream_index, frame)?;
+        let mut worker = message_event.write().map_err(|error| {
+          MessageError::RuntimeError(format!("Unable to access worker: {}", error))
+        })?;
+        let result = catch_panic(job.job_id, || {
+          worker.process_frame(job_result.clone(), stream_index, frame)
+        })?;
+        drop(worker);
+        metrics::frame_decoded(stream_index);
 
         output.push(result);
       }
       DecodeResult::WaitMore => {}
       DecodeResult::Nothing => {}
       DecodeResult::EndOfStream => {
-        message_event.borrow_mut().ending_process()?;
+        let mut worker = message_event.write().map_err(|error| {
+          MessageError::RuntimeError(format!("Unable to access worker: {}", error))
+        })?;
+        catch_panic(job.job_id, || worker.ending_process())?;
+        drop(worker);
 
         output.complete()?;
         let job_result = job_result.with_status(JobStatus::Completed);
@@ -1,9 +1,9 @@
 use std::sync::{
   mpsc,
   mpsc::{Receiver, Sender},
-  Arc, Mutex,
+  Arc, Mutex, RwLock,
 };
-use std::{cell::RefCell, collections::HashMap, io::Cursor, rc::Rc, thread};
+use std::{collections::HashMap, io::Cursor, thread};
 
 use ringbuf::RingBuffer;
 use schemars::JsonSchema;
@@ -21,6 +21,7 @@ use stainless_ffmpeg_sys::{
 use crate::{
   error::MessageError::RuntimeError,
   job::JobResult,
+  message::catch_panic,
   message::media::{ebu_ttml_live::EbuTtmlLiveDecoder, media_stream::MediaStream, srt::SrtStream},
   AudioFilter, MessageError, MessageEvent, ProcessFrame, ProcessResult, Result, VideoFilter,
 };
@@ -57,7 +58,7 @@ pub struct Source {
 
 impl Source {
   pub fn new<P: DeserializeOwned + JsonSchema, ME: MessageEvent<P>>(
-    message_event: Rc<RefCell<ME>>,
+    message_event: Arc<RwLock<ME>>,
     job_result: &JobResult,
     parameters: P,
     source_url: &str,
@@ -132,7 +133,7 @@ impl Source {
 
       let decoders = Self::get_decoders(
         message_event,
-        &job_result.get_str_job_id(),
+        job_result.get_job_id(),
         parameters,
         format_context.clone(),
         sender,
@@ -164,7 +165,7 @@ impl Source {
 
       let decoders = Self::get_decoders(
         message_event,
-        &job_result.get_str_job_id(),
+        job_result.get_job_id(),
         parameters,
         format_context.clone(),
         sender,
@@ -315,20 +316,23 @@ impl Source {
   }
 
   fn get_decoders<P: DeserializeOwned + JsonSchema, ME: MessageEvent<P>>(
-    message_event: Rc<RefCell<ME>>,
-    job_id: &str,
+    message_event: Arc<RwLock<ME>>,
+    job_id: u64,
     parameters: P,
     format_context: Arc<Mutex<FormatContext>>,
     sender: Arc<Mutex<Sender<ProcessResult>>>,
     start_index_ms: Option<i64>,
   ) -> Result<HashMap<usize, Decoder>> {
-    let selected_streams =
-      message_event
-        .borrow_mut()
-        .init_process(parameters, format_context.clone(), sender)?;
+    let mut worker = message_event
+      .write()
+      .map_err(|error| RuntimeError(format!("Unable to access worker: {}", error)))?;
+    let selected_streams = catch_panic(job_id, || {
+      worker.init_process(parameters, format_context.clone(), sender)
+    })?;
+    drop(worker);
 
     info!(
-      target: job_id,
+      target: &job_id.to_string(),
       "Selected stream IDs: {:?}", selected_streams
     );
 
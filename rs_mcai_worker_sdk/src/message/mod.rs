@@ -1,94 +1,434 @@
+mod abandoned;
 mod helpers;
+mod isolation;
 #[cfg(feature = "media")]
 pub mod media;
+mod processor_pool;
+mod progression_throttle;
+mod unwind;
 
+pub(crate) use isolation::process_child_job;
 #[cfg(feature = "media")]
 pub use media::{DESTINATION_PATH_PARAMETER, SOURCE_PATH_PARAMETER};
+pub(crate) use processor_pool::{InFlightJobs, ProcessorPool};
+pub(crate) use unwind::catch_panic;
 
 use crate::{
-  job::{Job, JobProgression, JobResult, JobStatus},
-  McaiChannel, MessageError, MessageEvent, Result,
+  channels::get_retry_delayed_exchange_name,
+  config::{
+    get_amqp_queue, get_job_isolation, get_job_max_retries, get_job_progression_interval,
+    get_job_retry_delays, get_workspace_keep_on_failure, get_workspace_root,
+  },
+  health,
+  job::{CancellationToken, Job, JobProgression, JobResult, JobStatus, JobStatusUpdate, Workspace},
+  metrics,
+  worker::docker::get_instance_id,
+  ErrorKind, JobError, McaiChannel, MessageError, MessageEvent, Result,
 };
 use lapin::{message::Delivery, options::*, BasicProperties, Promise};
 
 use schemars::JsonSchema;
 use serde::de::DeserializeOwned;
-use std::cell::RefCell;
-use std::rc::Rc;
+use serde_json::Value;
+use std::{
+  path::{Path, PathBuf},
+  sync::{
+    mpsc::{self, RecvTimeoutError},
+    Arc, Once, RwLock,
+  },
+  thread,
+  time::Duration,
+};
 
 static RESPONSE_EXCHANGE: &str = "job_response";
+/// Delay given to a timed out job to stop, once cancelled
+pub(crate) static TIMEOUT_GRACE_PERIOD: Duration = Duration::from_secs(5);
 static QUEUE_JOB_COMPLETED: &str = "job_completed";
 static QUEUE_JOB_ERROR: &str = "job_error";
 static QUEUE_JOB_PROGRESSION: &str = "job_progression";
+static QUEUE_JOB_STOPPED: &str = "job_stopped";
+static QUEUE_JOB_STATUS: &str = "job_status";
 
-pub fn process_message<P: DeserializeOwned + JsonSchema, ME: MessageEvent<P>>(
-  message_event: Rc<RefCell<ME>>,
+pub fn process_message<P, ME>(
+  message_event: Arc<RwLock<ME>>,
   message: Delivery,
   channel: McaiChannel,
-) -> Promise<()> {
-  let count = helpers::get_message_death_count(&message);
+  cancellation_token: CancellationToken,
+) -> Promise<()>
+where
+  P: DeserializeOwned + JsonSchema + Send + 'static,
+  ME: MessageEvent<P> + Send + Sync + 'static,
+{
+  let count = helpers::get_message_retry_count(&message);
   let message_data = std::str::from_utf8(&message.data).unwrap();
+  let job_id = helpers::get_message_job_id(&message);
+  metrics::job_received(job_id);
+
+  let (workspace, result) = match create_workspace(job_id) {
+    Ok(workspace) => {
+      let workspace_path = workspace.as_ref().map(Workspace::get_path);
+
+      let result = if get_job_isolation() {
+        debug!(
+          "Process job in a child process (iteration: {})",
+          count.unwrap_or(0)
+        );
+        isolation::process_in_child_process(
+          message_data,
+          Some(channel.clone()),
+          &cancellation_token,
+          workspace_path,
+        )
+      } else {
+        parse_and_process_message(
+          message_event,
+          message_data,
+          count,
+          Some(channel.clone()),
+          publish_job_progression,
+          cancellation_token.clone(),
+          workspace_path,
+        )
+      };
+      (workspace, result)
+    }
+    Err(error) => (None, Err(error)),
+  };
+
+  let stopped = cancellation_token.is_cancelled();
+  let published_in_error = is_published_in_error(&result, count);
+  let retried = result.is_err() && !published_in_error;
+  metrics::job_finished(job_id, &result, stopped, retried);
+  let published = if abandoned::is_abandoned() {
+    debug!("Job abandoned by the worker, its result is not published");
+    Promise::new_with_data(Ok(()))
+  } else {
+    publish_result(channel, message, result, &cancellation_token)
+  };
+
+  if let Some(workspace) = workspace {
+    workspace.clean(
+      !stopped && published_in_error,
+      get_workspace_keep_on_failure(),
+    );
+  }
+
+  published
+}
+
+/// Whether the job is finally published in error, a job retried later is not
+fn is_published_in_error(result: &Result<JobResult>, count: Option<i64>) -> bool {
+  match result {
+    Ok(job_result) => job_result.get_status() == &JobStatus::Error,
+    Err(error) => match error.get_kind() {
+      ErrorKind::Permanent => true,
+      ErrorKind::Transient | ErrorKind::Requirements => is_max_retries_reached(count.unwrap_or(0)),
+    },
+  }
+}
+
+/// A job retried `count` times is not retried anymore, but published in error
+fn is_max_retries_reached(count: i64) -> bool {
+  count >= get_job_max_retries()
+}
+
+/// Directory of the workspaces of the worker instance, in the root directory shared by the instances
+pub(crate) fn get_workspaces_directory() -> Option<PathBuf> {
+  get_workspace_root().map(|root| Path::new(&root).join(get_instance_id("/proc/self/cgroup")))
+}
+
+/// Create the workspace of the job, if a workspace root directory is configured
+pub(crate) fn create_workspace(job_id: Option<u64>) -> Result<Option<Workspace>> {
+  match (get_workspaces_directory(), job_id) {
+    (Some(directory), Some(job_id)) => {
+      Workspace::create(&directory, job_id)
+        .map(Some)
+        .map_err(|error| {
+          MessageError::RequirementsError(format!("Unable to create the job workspace: {}", error))
+        })
+    }
+    _ => Ok(None),
+  }
+}
+
+fn publish_result(
+  channel: McaiChannel,
+  message: Delivery,
+  result: Result<JobResult>,
+  cancellation_token: &CancellationToken,
+) -> Promise<()> {
+  if cancellation_token.is_cancelled() {
+    let job_result = match &result {
+      Ok(job_result) | Err(MessageError::ProcessingError(job_result)) => Some(job_result.clone()),
+      Err(_) => std::str::from_utf8(&message.data)
+        .ok()
+        .and_then(|message_data| Job::new(message_data).ok())
+        .map(JobResult::from),
+    };
+
+    if let Some(job_result) = job_result {
+      return publish_job_stopped(&channel, message.delivery_tag, job_result, false);
+    }
+  }
 
-  match parse_and_process_message(
-    message_event,
-    message_data,
-    count,
-    Some(channel.clone()),
-    publish_job_progression,
-  ) {
+  match result {
     Ok(job_result) => {
       info!(target: &job_result.get_str_job_id(), "Completed");
       publish_job_completed(channel, message, job_result)
     }
-    Err(error) => match error {
-      MessageError::RequirementsError(details) => {
-        publish_missing_requirements(channel, message, &details)
-      }
-      MessageError::NotImplemented() => publish_not_implemented(channel, message),
-      MessageError::ParameterValueError(error_message) => {
-        publish_parameter_error(channel, message, &error_message)
-      }
-      MessageError::ProcessingError(job_result) => {
-        publish_processing_error(channel, message, job_result)
+    Err(error) => match error.get_kind() {
+      ErrorKind::Transient => {
+        let details = get_error_details(&error);
+        warn!("Transient error: {}", details);
+        reject_for_retry(channel, message, &details, true)
       }
-      MessageError::RuntimeError(error_message) => {
-        publish_runtime_error(channel, message, &error_message)
+      ErrorKind::Requirements => {
+        publish_missing_requirements(channel, message, &get_error_details(&error))
       }
+      ErrorKind::Permanent => publish_permanent_error(channel, message, error),
     },
   }
 }
 
+fn get_error_details(error: &MessageError) -> String {
+  match error {
+    MessageError::RuntimeError(details)
+    | MessageError::ParameterValueError(details)
+    | MessageError::RequirementsError(details) => details.clone(),
+    MessageError::JobError(job_error) => job_error.get_message().to_string(),
+    error => format!("{:?}", error),
+  }
+}
+
+/// Publish an error that is not retried, and acknowledge the job message
+fn publish_permanent_error(
+  channel: McaiChannel,
+  message: Delivery,
+  error: MessageError,
+) -> Promise<()> {
+  match error {
+    MessageError::ProcessingError(job_result) => {
+      publish_processing_error(channel, message, job_result)
+    }
+    MessageError::RuntimeError(details) => publish_runtime_error(channel, message, &details),
+    MessageError::JobError(job_error) => publish_job_error(channel, message, job_error),
+    MessageError::NotImplemented() => {
+      let job_error = JobError::permanent("Not implemented feature").with_code("not_implemented");
+      publish_job_error(channel, message, job_error)
+    }
+    MessageError::ParameterValueError(details) => {
+      let job_error = JobError::permanent(&details).with_code("parameter_value_error");
+      publish_job_error(channel, message, job_error)
+    }
+    MessageError::RequirementsError(details) => {
+      let job_error = JobError::permanent(&details).with_code("requirements_error");
+      publish_job_error(channel, message, job_error)
+    }
+  }
+}
+
 pub fn parse_and_process_message<
-  P: DeserializeOwned + JsonSchema,
-  ME: MessageEvent<P>,
+  P: DeserializeOwned + JsonSchema + Send + 'static,
+  ME: MessageEvent<P> + Send + Sync + 'static,
   F: Fn(Option<McaiChannel>, u64, u8) -> Result<()> + 'static,
 >(
-  message_event: Rc<RefCell<ME>>,
+  message_event: Arc<RwLock<ME>>,
   message_data: &str,
   count: Option<i64>,
   channel: Option<McaiChannel>,
   publish_job_progression: F,
+  cancellation_token: CancellationToken,
+  workspace: Option<&Path>,
 ) -> Result<JobResult> {
-  let job = Job::new(message_data)?;
+  let mut job = Job::new(message_data)?;
+  if let Some(workspace) = workspace {
+    job.set_workspace(workspace);
+  }
   debug!(target: &job.job_id.to_string(),
          "received message: {:?} (iteration: {})",
          job,
          count.unwrap_or(0));
 
-  job.check_requirements()?;
-  let parameters: P = job.get_parameters()?;
+  let initializing = JobStatusUpdate::new(job.job_id, None, JobStatus::Initializing);
+  publish_job_status(channel.clone(), initializing);
+
+  let parameters = job
+    .check_requirements()
+    .and_then(|_| job.get_processed_parameters::<P>());
+
+  let parameters = match parameters {
+    Ok(parameters) => parameters,
+    Err(error) => {
+      let status = get_final_status(Err(&error), &cancellation_token);
+      let update = JobStatusUpdate::new(job.job_id, Some(JobStatus::Initializing), status);
+      publish_job_status(channel, update);
+      return Err(error);
+    }
+  };
 
   publish_job_progression(channel.clone(), job.job_id, 0)?;
 
-  let job_result = JobResult::new(job.job_id);
+  let running = JobStatusUpdate::new(
+    job.job_id,
+    Some(JobStatus::Initializing),
+    JobStatus::Running,
+  );
+  publish_job_status(channel.clone(), running);
+
+  let job_result = JobResult::new(job.job_id).with_cancellation_token(cancellation_token.child());
+
+  let result = match job.get_timeout() {
+    Some(timeout) => process_job_with_timeout(
+      message_event,
+      channel.clone(),
+      job.clone(),
+      parameters,
+      job_result,
+      timeout,
+    ),
+    None => process_job(message_event, channel.clone(), &job, parameters, job_result),
+  };
+
+  flush_job_progression(job.job_id);
 
+  let status = get_final_status(result.as_ref(), &cancellation_token);
+  let update = JobStatusUpdate::new(job.job_id, Some(JobStatus::Running), status);
+  publish_job_status(channel, update);
+
+  result
+}
+
+fn process_job<P: DeserializeOwned + JsonSchema, ME: MessageEvent<P>>(
+  message_event: Arc<RwLock<ME>>,
+  channel: Option<McaiChannel>,
+  job: &Job,
+  parameters: P,
+  job_result: JobResult,
+) -> Result<JobResult> {
   #[cfg(feature = "media")]
-  return media::process(message_event, channel, &job, parameters, job_result);
+  let result = media::process(message_event, channel, job, parameters, job_result);
 
   #[cfg(not(feature = "media"))]
-  message_event
-    .borrow_mut()
-    .process(channel, parameters, job_result)
+  let result = message_event
+    .read()
+    .map_err(|error| MessageError::RuntimeError(format!("Unable to access worker: {}", error)))
+    .and_then(|message_event| {
+      catch_panic(job.job_id, || {
+        message_event.process(channel, parameters, job_result)
+      })
+    });
+
+  result
+}
+
+/// Process the job in a dedicated thread, cancelled if it does not end before the timeout
+///
+/// On timeout, the thread can not publish anything anymore and the cancellation token of the job
+/// result is cancelled. The thread is given a grace period to stop before the job is published
+/// in error. A thread still running after it may keep the worker locked, the worker is then
+/// reported as stalled to be restarted.
+fn process_job_with_timeout<P, ME>(
+  message_event: Arc<RwLock<ME>>,
+  channel: Option<McaiChannel>,
+  job: Job,
+  parameters: P,
+  job_result: JobResult,
+  timeout: Duration,
+) -> Result<JobResult>
+where
+  P: DeserializeOwned + JsonSchema + Send + 'static,
+  ME: MessageEvent<P> + Send + Sync + 'static,
+{
+  let job_id = job.job_id;
+  let cancellation_token = job_result.get_cancellation_token().clone();
+  let (sender, receiver) = mpsc::channel();
+
+  let handle = thread::Builder::new()
+    .name(format!("job_{}", job_id))
+    .spawn(move || {
+      let result = process_job(message_event, channel, &job, parameters, job_result);
+      // the receiver is dropped if the job was abandoned
+      let _ = sender.send(result);
+      abandoned::release();
+    })
+    .map_err(|error| {
+      MessageError::RuntimeError(format!("Unable to start job processing thread: {}", error))
+    })?;
+  // the thread is abandoned with the job, if it is requeued
+  abandoned::attach(handle.thread().id());
+
+  match receiver.recv_timeout(timeout) {
+    Ok(result) => result,
+    Err(RecvTimeoutError::Timeout) => {
+      abandoned::abandon(handle.thread().id());
+      cancellation_token.cancel();
+      let message = format!("Job timed out after {} ms", timeout.as_millis());
+      error!(target: &job_id.to_string(), "{}", message);
+
+      if receiver.recv_timeout(TIMEOUT_GRACE_PERIOD).is_err() {
+        error!(target: &job_id.to_string(),
+               "Job still running {} ms after its cancellation, it is abandoned",
+               TIMEOUT_GRACE_PERIOD.as_millis());
+        health::set_stalled();
+      }
+      Err(JobError::permanent(&message).with_code("timeout").into())
+    }
+    Err(RecvTimeoutError::Disconnected) => Err(MessageError::RuntimeError(
+      "Job processing thread stopped unexpectedly".to_string(),
+    )),
+  }
+}
+
+/// Status of a job at the end of its processing
+///
+/// A job that will be retried is paused.
+fn get_final_status(
+  result: std::result::Result<&JobResult, &MessageError>,
+  cancellation_token: &CancellationToken,
+) -> JobStatus {
+  if cancellation_token.is_cancelled() {
+    return JobStatus::Stopped;
+  }
+
+  match result {
+    Ok(job_result) => match job_result.get_status() {
+      JobStatus::Unknown | JobStatus::Initializing | JobStatus::Running => JobStatus::Completed,
+      status => status.clone(),
+    },
+    Err(error) => match error.get_kind() {
+      ErrorKind::Permanent => JobStatus::Error,
+      ErrorKind::Transient | ErrorKind::Requirements => JobStatus::Paused,
+    },
+  }
+}
+
+/// Function to publish the transition of a job from a status to another one
+pub fn publish_job_status(channel: Option<McaiChannel>, job_status_update: JobStatusUpdate) {
+  if abandoned::is_abandoned() {
+    return;
+  }
+  let job_id = job_status_update.get_job_id().to_string();
+
+  if let Some(channel) = channel {
+    let msg = json!(job_status_update).to_string();
+
+    if let Err(error) = channel
+      .basic_publish(
+        RESPONSE_EXCHANGE,
+        QUEUE_JOB_STATUS,
+        BasicPublishOptions::default(),
+        msg.as_bytes().to_vec(),
+        BasicProperties::default(),
+      )
+      .wait()
+    {
+      error!(target: &job_id, "Unable to publish job status: {:?}", error);
+    }
+  } else if isolation::is_child_process() {
+    isolation::send_status(job_status_update);
+  } else {
+    info!(target: &job_id, "status: {:?}", job_status_update.get_status());
+  }
 }
 
 fn publish_job_completed(
@@ -130,8 +470,73 @@ pub fn publish_job_progression(
   job_id: u64,
   progression: u8,
 ) -> Result<()> {
+  publish_job_progression_details(channel, JobProgression::new(job_id, progression))
+}
+
+/// Function to publish a progression event, with the current step and an estimated remaining time
+///
+/// Progressions are coalesced to publish at most one of them per `JOB_PROGRESSION_INTERVAL`.
+pub fn publish_job_progression_details(
+  channel: Option<McaiChannel>,
+  job_progression: JobProgression,
+) -> Result<()> {
+  if abandoned::is_abandoned() {
+    return Ok(());
+  }
+  metrics::job_progression(
+    job_progression.get_job_id(),
+    job_progression.get_progression(),
+  );
+  start_progression_flusher();
+
+  let interval = Duration::from_millis(get_job_progression_interval());
+  match progression_throttle::submit(channel, job_progression, interval) {
+    Some((channel, job_progression)) => send_job_progression(channel, job_progression),
+    None => Ok(()),
+  }
+}
+
+/// Publish the pending progression of a finished job
+pub(crate) fn flush_job_progression(job_id: u64) {
+  if let Some((channel, job_progression)) = progression_throttle::release(job_id) {
+    if let Err(error) = send_job_progression(channel, job_progression) {
+      error!(target: &job_id.to_string(), "Unable to publish job progression: {:?}", error);
+    }
+  }
+}
+
+/// Publish the pending progressions at the end of their interval, if their job reports nothing new
+fn start_progression_flusher() {
+  static PROGRESSION_FLUSHER: Once = Once::new();
+
+  PROGRESSION_FLUSHER.call_once(|| {
+    let spawned = thread::Builder::new()
+      .name("progression_flusher".to_string())
+      .spawn(|| loop {
+        thread::sleep(Duration::from_millis(100));
+
+        let interval = Duration::from_millis(get_job_progression_interval());
+        for (channel, job_progression) in progression_throttle::take_due(interval) {
+          if let Err(error) = send_job_progression(channel, job_progression) {
+            error!("Unable to publish job progression: {:?}", error);
+          }
+        }
+      });
+
+    if let Err(error) = spawned {
+      error!("Unable to start the progression flusher: {}", error);
+    }
+  });
+}
+
+fn send_job_progression(
+  channel: Option<McaiChannel>,
+  job_progression: JobProgression,
+) -> Result<()> {
+  let job_id = job_progression.get_job_id();
+
   if let Some(channel) = channel {
-    let msg = json!(JobProgression::new(job_id, progression)).to_string();
+    let msg = json!(job_progression).to_string();
 
     channel
       .basic_publish(
@@ -149,32 +554,155 @@ pub fn publish_job_progression(
         MessageError::ProcessingError(result)
       })
       .map(|_| ())
+  } else if isolation::is_child_process() {
+    isolation::send_progression(job_progression);
+    Ok(())
   } else {
-    info!(target: &job_id.to_string(), "progression: {}%", progression);
+    info!(target: &job_id.to_string(), "progression: {}%", job_progression.get_progression());
+    crate::source_orders::write_progression(&job_progression);
     Ok(())
   }
 }
 
+/// Publish the stopped status of an interrupted job
+///
+/// A job interrupted by the worker termination is requeued to be processed by another instance,
+/// whereas a cancelled job is acknowledged.
+pub(crate) fn publish_job_stopped(
+  channel: &McaiChannel,
+  delivery_tag: u64,
+  job_result: JobResult,
+  requeue: bool,
+) -> Promise<()> {
+  warn!(target: &job_result.get_str_job_id(), "Job stopped");
+
+  let content = json!(job_result.with_status(JobStatus::Stopped)).to_string();
+
+  if let Err(error) = channel
+    .basic_publish(
+      RESPONSE_EXCHANGE,
+      QUEUE_JOB_STOPPED,
+      BasicPublishOptions::default(),
+      content.as_bytes().to_vec(),
+      BasicProperties::default(),
+    )
+    .wait()
+  {
+    error!("Unable to publish stopped job: {:?}", error);
+  }
+
+  if requeue {
+    channel.basic_nack(
+      delivery_tag,
+      BasicNackOptions {
+        multiple: false,
+        requeue: true,
+      },
+    )
+  } else {
+    channel.basic_ack(delivery_tag, BasicAckOptions::default())
+  }
+}
+
 fn publish_missing_requirements(
   channel: McaiChannel,
   message: Delivery,
   details: &str,
 ) -> Promise<()> {
   debug!("{}", details);
-  channel.basic_reject(message.delivery_tag, BasicRejectOptions::default())
+  reject_for_retry(channel, message, details, false)
 }
 
-fn publish_not_implemented(channel: McaiChannel, message: Delivery) -> Promise<()> {
-  error!("Not implemented feature");
-  channel.basic_reject(
-    message.delivery_tag,
-    BasicRejectOptions { requeue: true }, /*requeue*/
-  )
+/// Retry the job later, through a delayed queue
+///
+/// With `backoff`, the delay is chosen from the number of retries, otherwise the shortest delay is used.
+/// Once the maximum number of retries is reached, the job is published in error.
+/// The retries are counted in a header of the republished message.
+fn reject_for_retry(
+  channel: McaiChannel,
+  message: Delivery,
+  details: &str,
+  backoff: bool,
+) -> Promise<()> {
+  let count = helpers::get_message_retry_count(&message).unwrap_or(0);
+  if is_max_retries_reached(count) {
+    return publish_max_retries_exceeded(channel, message, count, details);
+  }
+
+  let delays = get_job_retry_delays();
+  let delay = if backoff {
+    delays[std::cmp::min(count as usize, delays.len() - 1)]
+  } else {
+    delays[0]
+  };
+  debug!("Retry job in {} ms (retry {})", delay, count + 1);
+
+  if let Err(error) = channel
+    .basic_publish(
+      &get_retry_delayed_exchange_name(delay),
+      &get_amqp_queue(),
+      BasicPublishOptions::default(),
+      message.data.clone(),
+      helpers::with_retry_count(&message.properties, count + 1),
+    )
+    .wait()
+  {
+    error!("Unable to delay the job retry: {:?}", error);
+    return channel.basic_reject(message.delivery_tag, BasicRejectOptions::default());
+  }
+
+  channel.basic_ack(message.delivery_tag, BasicAckOptions::default())
 }
 
-fn publish_parameter_error(channel: McaiChannel, message: Delivery, details: &str) -> Promise<()> {
-  debug!("Parameter value error: {}", details);
-  channel.basic_reject(message.delivery_tag, BasicRejectOptions::default())
+fn publish_max_retries_exceeded(
+  channel: McaiChannel,
+  message: Delivery,
+  count: i64,
+  details: &str,
+) -> Promise<()> {
+  let job_id = helpers::get_message_job_id(&message);
+
+  error!(
+    "Max retries exceeded for job {:?} after {} retries: {}",
+    job_id, count, details
+  );
+
+  // the job was paused while waiting for its retry
+  if let Some(job_id) = job_id {
+    let update = JobStatusUpdate::new(job_id, Some(JobStatus::Paused), JobStatus::Error);
+    publish_job_status(Some(channel.clone()), update);
+  }
+
+  let content = json!({
+    "job_id": job_id,
+    "status": "error",
+    "message": format!("max retries exceeded ({} retries)", count),
+    "last_error": details,
+    "attempts": helpers::get_message_death_history(&message),
+  })
+  .to_string();
+
+  if channel
+    .basic_publish(
+      RESPONSE_EXCHANGE,
+      QUEUE_JOB_ERROR,
+      BasicPublishOptions::default(),
+      content.as_bytes().to_vec(),
+      BasicProperties::default(),
+    )
+    .wait()
+    .is_ok()
+  {
+    channel.basic_ack(
+      message.delivery_tag,
+      BasicAckOptions::default(), /*not requeue*/
+    )
+  } else {
+    channel.basic_reject(
+      message.delivery_tag,
+      BasicRejectOptions { requeue: true }, /*requeue*/
+    )
+  }
 }
 
 fn publish_processing_error(
@@ -212,6 +740,50 @@ fn publish_processing_error(
   }
 }
 
+/// Payload of the `job_error` queue, with the code and the details of the error
+fn get_job_error_content(job_id: Option<u64>, job_error: &JobError) -> Value {
+  let mut content = match job_id {
+    Some(job_id) => json!(JobResult::new(job_id)
+      .with_status(JobStatus::Error)
+      .with_message(job_error.get_message())),
+    None => json!({
+      "status": "error",
+      "message": job_error.get_message()
+    }),
+  };
+  content["error"] = json!(job_error);
+  content
+}
+
+fn publish_job_error(channel: McaiChannel, message: Delivery, job_error: JobError) -> Promise<()> {
+  let job_id = helpers::get_message_job_id(&message);
+  error!("Job {:?} returned in error: {:?}", job_id, job_error);
+
+  let content = get_job_error_content(job_id, &job_error).to_string();
+
+  if channel
+    .basic_publish(
+      RESPONSE_EXCHANGE,
+      QUEUE_JOB_ERROR,
+      BasicPublishOptions::default(),
+      content.as_bytes().to_vec(),
+      BasicProperties::default(),
+    )
+    .wait()
+    .is_ok()
+  {
+    channel.basic_ack(
+      message.delivery_tag,
+      BasicAckOptions::default(), /*not requeue*/
+    )
+  } else {
+    channel.basic_reject(
+      message.delivery_tag,
+      BasicRejectOptions { requeue: true }, /*requeue*/
+    )
+  }
+}
+
 fn publish_runtime_error(channel: McaiChannel, message: Delivery, details: &str) -> Promise<()> {
   error!("An error occurred: {:?}", details);
   let content = json!({
@@ -242,3 +814,177 @@ fn publish_runtime_error(channel: McaiChannel, message: Delivery, details: &str)
     )
   }
 }
+
+#[cfg(not(feature = "media"))]
+#[test]
+fn process_job_timeout() {
+  use schemars::JsonSchema;
+
+  #[derive(Deserialize, JsonSchema)]
+  struct SleepParameters {
+    duration_ms: u64,
+  }
+
+  struct SleepWorker {}
+
+  impl MessageEvent<SleepParameters> for SleepWorker {
+    fn get_name(&self) -> String {
+      "sleep".to_string()
+    }
+    fn get_short_description(&self) -> String {
+      "Sleep".to_string()
+    }
+    fn get_description(&self) -> String {
+      "Sleep for a while".to_string()
+    }
+    fn get_version(&self) -> semver::Version {
+      semver::Version::new(1, 0, 0)
+    }
+
+    fn process(
+      &self,
+      _channel: Option<McaiChannel>,
+      parameters: SleepParameters,
+      job_result: JobResult,
+    ) -> Result<JobResult> {
+      for _ in 0..parameters.duration_ms / 10 {
+        if job_result.is_cancelled() {
+          return Ok(job_result.with_status(JobStatus::Stopped));
+        }
+        thread::sleep(Duration::from_millis(10));
+      }
+      Ok(job_result.with_status(JobStatus::Completed))
+    }
+  }
+
+  let message_event = Arc::new(RwLock::new(SleepWorker {}));
+  let message = |duration_ms: u64| {
+    format!(
+      r#"{{
+        "job_id": 456,
+        "parameters": [
+          {{ "id": "duration_ms", "type": "integer", "value": {} }},
+          {{ "id": "sdk_timeout_ms", "type": "integer", "value": 200 }}
+        ]
+      }}"#,
+      duration_ms
+    )
+  };
+
+  let result = parse_and_process_message(
+    message_event.clone(),
+    &message(10),
+    None,
+    None,
+    |_, _, _| Ok(()),
+    CancellationToken::default(),
+    None,
+  );
+  assert!(result.is_ok());
+
+  let cancellation_token = CancellationToken::default();
+  let result = parse_and_process_message(
+    message_event.clone(),
+    &message(2000),
+    None,
+    None,
+    |_, _, _| Ok(()),
+    cancellation_token.clone(),
+    None,
+  );
+  let error = JobError::permanent("Job timed out after 200 ms").with_code("timeout");
+  assert_eq!(result, Err(MessageError::JobError(error)));
+  assert!(!cancellation_token.is_cancelled());
+  // the job stopped within the grace period, and the worker can be accessed again
+  assert!(message_event.try_write().is_ok());
+}
+
+#[cfg(not(feature = "media"))]
+#[test]
+fn job_error_parameter_violations() {
+  use schemars::JsonSchema;
+
+  #[derive(Deserialize, JsonSchema)]
+  struct CountParameters {
+    #[allow(dead_code)]
+    #[schemars(range(min = 1))]
+    count: u64,
+  }
+
+  struct CountWorker {}
+
+  impl MessageEvent<CountParameters> for CountWorker {
+    fn get_name(&self) -> String {
+      "count".to_string()
+    }
+    fn get_short_description(&self) -> String {
+      "Count".to_string()
+    }
+    fn get_description(&self) -> String {
+      "Count something".to_string()
+    }
+    fn get_version(&self) -> semver::Version {
+      semver::Version::new(1, 0, 0)
+    }
+  }
+
+  let message = r#"{
+    "job_id": 789,
+    "parameters": [
+      { "id": "count", "type": "integer", "value": 0 }
+    ]
+  }"#;
+
+  let result = parse_and_process_message(
+    Arc::new(RwLock::new(CountWorker {})),
+    message,
+    None,
+    None,
+    |_, _, _| Ok(()),
+    CancellationToken::default(),
+    None,
+  );
+
+  let job_error = match result {
+    Err(MessageError::JobError(job_error)) => job_error,
+    result => panic!("unexpected result: {:?}", result),
+  };
+  let content = get_job_error_content(Some(789), &job_error);
+
+  assert_eq!(content["job_id"], json!(789));
+  assert_eq!(content["status"], json!("error"));
+  assert_eq!(
+    content["error"],
+    json!({
+      "kind": "permanent",
+      "message": "Invalid parameters: /count: 0 is lower than the minimum 1",
+      "code": "parameter_value_error",
+      "details": [
+        { "path": "/count", "message": "0 is lower than the minimum 1" }
+      ]
+    })
+  );
+}
+
+#[test]
+fn job_published_in_error() {
+  let max_retries = get_job_max_retries();
+  let transient = || Err(JobError::transient("unavailable").into());
+
+  assert!(!is_published_in_error(&Ok(JobResult::new(1)), None));
+  assert!(is_published_in_error(
+    &Ok(JobResult::new(1).with_status(JobStatus::Error)),
+    None
+  ));
+  assert!(is_published_in_error(
+    &Err(JobError::permanent("invalid").into()),
+    None
+  ));
+  assert!(!is_published_in_error(&transient(), None));
+  assert!(!is_published_in_error(&transient(), Some(max_retries - 1)));
+  assert!(is_published_in_error(&transient(), Some(max_retries)));
+  assert!(is_published_in_error(
+    &Err(MessageError::RequirementsError("missing".to_string())),
+    Some(max_retries)
+  ));
+}
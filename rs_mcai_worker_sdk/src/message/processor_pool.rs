@@ -0,0 +1,485 @@
+use crate::{
+  health,
+  job::{CancellationToken, JobResult},
+  message::{abandoned, helpers::get_message_job_id, process_message, publish_job_stopped},
+  worker::control::WorkerControl,
+  McaiChannel, MessageEvent,
+};
+use futures::channel::mpsc::UnboundedSender;
+use lapin::{message::Delivery, options::BasicNackOptions};
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use std::{
+  collections::HashMap,
+  sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    mpsc::{channel, Sender},
+    Arc, Mutex, RwLock,
+  },
+  thread::{self, ThreadId},
+  time::{Duration, Instant},
+};
+
+/// Channel on which the deliveries processed by the pool are acknowledged
+pub trait DeliveryChannel: Clone + Send + 'static {
+  /// Requeue a delivery, publishing the stopped status of its job if it is set
+  fn requeue(&self, delivery_tag: u64, job_result: Option<JobResult>) -> lapin::Result<()>;
+}
+
+impl DeliveryChannel for McaiChannel {
+  fn requeue(&self, delivery_tag: u64, job_result: Option<JobResult>) -> lapin::Result<()> {
+    match job_result {
+      Some(job_result) => publish_job_stopped(self, delivery_tag, job_result, true).wait(),
+      None => {
+        let options = BasicNackOptions {
+          multiple: false,
+          requeue: true,
+        };
+        self.basic_nack(delivery_tag, options).wait()
+      }
+    }
+  }
+}
+
+/// Delivery currently processed by a processor thread
+struct InFlightJob<C> {
+  delivery_tag: u64,
+  job_id: Option<u64>,
+  channel: C,
+  cancellation_token: CancellationToken,
+  thread_id: ThreadId,
+}
+
+/// Jobs currently processed by the processor threads
+#[derive(Clone)]
+pub struct InFlightJobs<C = McaiChannel> {
+  jobs: Arc<Mutex<HashMap<u64, InFlightJob<C>>>>,
+}
+
+impl<C> Default for InFlightJobs<C> {
+  fn default() -> Self {
+    InFlightJobs {
+      jobs: Arc::new(Mutex::new(HashMap::new())),
+    }
+  }
+}
+
+impl<C> InFlightJobs<C> {
+  /// Request the cancellation of a running job
+  ///
+  /// Returns `false` if the job is not processed by this worker instance.
+  pub fn cancel(&self, job_id: u64) -> bool {
+    let jobs = self.jobs.lock().unwrap();
+    let mut found = false;
+    for in_flight_job in jobs.values() {
+      if in_flight_job.job_id == Some(job_id) {
+        in_flight_job.cancellation_token.cancel();
+        found = true;
+      }
+    }
+    found
+  }
+
+  fn insert(&self, identifier: u64, in_flight_job: InFlightJob<C>) {
+    self.jobs.lock().unwrap().insert(identifier, in_flight_job);
+  }
+
+  fn remove(&self, identifier: u64) {
+    self.jobs.lock().unwrap().remove(&identifier);
+  }
+
+  fn len(&self) -> usize {
+    self.jobs.lock().unwrap().len()
+  }
+}
+
+/// Pool of threads processing the received job messages
+///
+/// Each thread processes one delivery at a time, so the pool size is the number of jobs
+/// the worker can handle concurrently. It must match the channel prefetch count.
+pub struct ProcessorPool<C = McaiChannel> {
+  sender: Sender<(Delivery, C, u64)>,
+  in_flight_jobs: InFlightJobs<C>,
+  stopping: Arc<AtomicBool>,
+  /// Incremented when the connection is lost, the messages of a previous connection are skipped
+  connection: Arc<AtomicU64>,
+}
+
+impl ProcessorPool {
+  /// A stalled job may keep the worker locked: the pool stops to process new messages,
+  /// and requests the worker termination with the control sender.
+  pub fn new<P, ME>(
+    concurrency: u16,
+    message_event: Arc<RwLock<ME>>,
+    control_sender: UnboundedSender<WorkerControl>,
+  ) -> Self
+  where
+    P: DeserializeOwned + JsonSchema + Send + 'static,
+    ME: MessageEvent<P> + Send + Sync + 'static,
+  {
+    ProcessorPool::with_processor(
+      concurrency,
+      control_sender,
+      move |delivery, channel, cancellation_token| {
+        process_message(message_event.clone(), delivery, channel, cancellation_token).wait()
+      },
+    )
+  }
+}
+
+impl<C: DeliveryChannel> ProcessorPool<C> {
+  /// Start the threads processing and acknowledging the deliveries with the `processor` function
+  fn with_processor<F>(
+    concurrency: u16,
+    control_sender: UnboundedSender<WorkerControl>,
+    processor: F,
+  ) -> Self
+  where
+    F: Fn(Delivery, C, CancellationToken) -> lapin::Result<()> + Send + Sync + 'static,
+  {
+    let (sender, receiver) = channel::<(Delivery, C, u64)>();
+    let receiver = Arc::new(Mutex::new(receiver));
+    let processor = Arc::new(processor);
+    let in_flight_jobs = InFlightJobs::default();
+    let stopping = Arc::new(AtomicBool::new(false));
+    let connection = Arc::new(AtomicU64::new(0));
+    let identifier_sequence = Arc::new(AtomicU64::new(0));
+
+    for index in 0..concurrency {
+      let receiver = receiver.clone();
+      let processor = processor.clone();
+      let in_flight_jobs = in_flight_jobs.clone();
+      let stopping = stopping.clone();
+      let connection = connection.clone();
+      let identifier_sequence = identifier_sequence.clone();
+      let control_sender = control_sender.clone();
+
+      thread::Builder::new()
+        .name(format!("processor_{}", index))
+        .spawn(move || loop {
+          let message = receiver.lock().unwrap().recv();
+
+          match message {
+            Ok((delivery, channel, delivery_connection)) => {
+              if delivery_connection != connection.load(Ordering::SeqCst) {
+                debug!("Message received on a lost connection, skip it");
+                continue;
+              }
+
+              if stopping.load(Ordering::SeqCst) {
+                debug!("Worker is stopping, requeue message");
+                if let Err(error) = channel.requeue(delivery.delivery_tag, None) {
+                  error!("Unable to requeue message: {:?}", error);
+                }
+                continue;
+              }
+
+              let identifier = identifier_sequence.fetch_add(1, Ordering::SeqCst);
+              let cancellation_token = CancellationToken::default();
+              let in_flight_job = InFlightJob {
+                delivery_tag: delivery.delivery_tag,
+                job_id: get_message_job_id(&delivery),
+                channel: channel.clone(),
+                cancellation_token: cancellation_token.clone(),
+                thread_id: thread::current().id(),
+              };
+              in_flight_jobs.insert(identifier, in_flight_job);
+
+              // the connection may be lost while registering the job, before it is abandoned
+              if delivery_connection != connection.load(Ordering::SeqCst) {
+                in_flight_jobs.remove(identifier);
+                continue;
+              }
+
+              if let Err(error) = processor(delivery, channel, cancellation_token) {
+                error!("Unable to acknowledge message: {:?}", error);
+              }
+
+              in_flight_jobs.remove(identifier);
+              abandoned::release();
+
+              if health::is_stalled() && !stopping.swap(true, Ordering::SeqCst) {
+                error!("A timed out job is still running, stop the worker to release it");
+                if let Err(error) = control_sender.unbounded_send(WorkerControl::Terminate) {
+                  error!("Unable to request the worker termination: {:?}", error);
+                }
+              }
+            }
+            Err(_) => {
+              debug!("Processor #{} stopped", index);
+              break;
+            }
+          }
+        })
+        .expect("unable to start processor thread");
+    }
+
+    ProcessorPool {
+      sender,
+      in_flight_jobs,
+      stopping,
+      connection,
+    }
+  }
+
+  pub fn get_in_flight_jobs(&self) -> InFlightJobs<C> {
+    self.in_flight_jobs.clone()
+  }
+
+  pub fn process(&self, delivery: Delivery, channel: C) {
+    let connection = self.connection.load(Ordering::SeqCst);
+    if let Err(error) = self.sender.send((delivery, channel, connection)) {
+      error!("Unable to submit message to processors: {:?}", error);
+    }
+  }
+
+  /// Stop to process new messages, and wait for the in-flight jobs to be finished
+  ///
+  /// Returns `false` if some jobs are still running at the end of the grace period.
+  pub fn drain(&self, grace_period: Duration) -> bool {
+    self.stopping.store(true, Ordering::SeqCst);
+
+    let start = Instant::now();
+    loop {
+      let count = self.in_flight_jobs.len();
+      if count == 0 {
+        return true;
+      }
+      if start.elapsed() >= grace_period {
+        warn!("{} job(s) still running after the grace period", count);
+        return false;
+      }
+      thread::sleep(Duration::from_millis(100));
+    }
+  }
+
+  /// Requeue the in-flight jobs, publishing their stopped status
+  ///
+  /// The jobs are cancelled and abandoned first, with the threads processing them, so they
+  /// do not publish anything nor acknowledge their message once they end.
+  /// Returns the delivery tags of the requeued jobs.
+  pub fn requeue_in_flight_jobs(&self) -> Vec<u64> {
+    let mut delivery_tags = vec![];
+
+    for in_flight_job in self.take_in_flight_jobs() {
+      let job_result = in_flight_job.job_id.map(|job_id| {
+        JobResult::new(job_id).with_message("Job interrupted by the worker termination")
+      });
+
+      if let Err(error) = in_flight_job
+        .channel
+        .requeue(in_flight_job.delivery_tag, job_result)
+      {
+        error!("Unable to requeue in-flight job: {:?}", error);
+      }
+      delivery_tags.push(in_flight_job.delivery_tag);
+    }
+
+    delivery_tags.sort_unstable();
+    delivery_tags
+  }
+
+  /// Abandon the in-flight jobs and the pending messages of a lost connection, before reconnecting
+  ///
+  /// Their messages can not be acknowledged anymore, the server delivers them again: the jobs
+  /// are cancelled and abandoned so they do not run twice at once.
+  /// Returns the delivery tags of the abandoned jobs.
+  pub fn abandon_in_flight_jobs(&self) -> Vec<u64> {
+    self.connection.fetch_add(1, Ordering::SeqCst);
+
+    let mut delivery_tags: Vec<u64> = self
+      .take_in_flight_jobs()
+      .iter()
+      .map(|in_flight_job| in_flight_job.delivery_tag)
+      .collect();
+
+    delivery_tags.sort_unstable();
+    delivery_tags
+  }
+
+  /// Remove the in-flight jobs, cancelled and abandoned with their processing threads
+  fn take_in_flight_jobs(&self) -> Vec<InFlightJob<C>> {
+    let mut jobs = self.in_flight_jobs.jobs.lock().unwrap();
+
+    jobs
+      .drain()
+      .map(|(_identifier, in_flight_job)| {
+        abandoned::abandon(in_flight_job.thread_id);
+        in_flight_job.cancellation_token.cancel();
+        in_flight_job
+      })
+      .collect()
+  }
+}
+
+/// Channel recording the requeued deliveries
+#[cfg(test)]
+#[derive(Clone, Default)]
+struct TestChannel {
+  requeued: Arc<Mutex<Vec<(u64, Option<u64>)>>>,
+}
+
+#[cfg(test)]
+impl DeliveryChannel for TestChannel {
+  fn requeue(&self, delivery_tag: u64, job_result: Option<JobResult>) -> lapin::Result<()> {
+    let job_id = job_result.map(|job_result| job_result.get_job_id());
+    self.requeued.lock().unwrap().push((delivery_tag, job_id));
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+fn get_delivery(delivery_tag: u64) -> Delivery {
+  Delivery {
+    delivery_tag,
+    exchange: "".into(),
+    routing_key: "job_test".into(),
+    redelivered: false,
+    properties: lapin::BasicProperties::default(),
+    data: format!(r#"{{"job_id": {}, "parameters": []}}"#, delivery_tag * 100).into_bytes(),
+  }
+}
+
+#[cfg(test)]
+fn wait_for(condition: impl Fn() -> bool) {
+  let start = Instant::now();
+  while !condition() {
+    assert!(
+      start.elapsed() < Duration::from_secs(5),
+      "condition not met"
+    );
+    thread::sleep(Duration::from_millis(10));
+  }
+}
+
+#[test]
+fn processor_pool_concurrency() {
+  let running = Arc::new(AtomicU64::new(0));
+  let max_running = Arc::new(AtomicU64::new(0));
+  let processed = Arc::new(AtomicU64::new(0));
+
+  let (control_sender, _control_receiver) = futures::channel::mpsc::unbounded();
+  let pool = {
+    let running = running.clone();
+    let max_running = max_running.clone();
+    let processed = processed.clone();
+    ProcessorPool::with_processor(3, control_sender, move |_delivery, _channel, _token| {
+      let count = running.fetch_add(1, Ordering::SeqCst) + 1;
+      max_running.fetch_max(count, Ordering::SeqCst);
+      thread::sleep(Duration::from_millis(200));
+      running.fetch_sub(1, Ordering::SeqCst);
+      processed.fetch_add(1, Ordering::SeqCst);
+      Ok(())
+    })
+  };
+
+  let channel = TestChannel::default();
+  for delivery_tag in 1..=6 {
+    pool.process(get_delivery(delivery_tag), channel.clone());
+  }
+
+  wait_for(|| processed.load(Ordering::SeqCst) == 6);
+  assert_eq!(max_running.load(Ordering::SeqCst), 3);
+  assert!(channel.requeued.lock().unwrap().is_empty());
+}
+
+#[test]
+fn processor_pool_drain() {
+  let processed = Arc::new(AtomicU64::new(0));
+
+  let (control_sender, _control_receiver) = futures::channel::mpsc::unbounded();
+  let pool = {
+    let processed = processed.clone();
+    ProcessorPool::with_processor(2, control_sender, move |_delivery, _channel, _token| {
+      thread::sleep(Duration::from_millis(300));
+      processed.fetch_add(1, Ordering::SeqCst);
+      Ok(())
+    })
+  };
+
+  let channel = TestChannel::default();
+  pool.process(get_delivery(1), channel.clone());
+  pool.process(get_delivery(2), channel.clone());
+  let in_flight_jobs = pool.get_in_flight_jobs();
+  wait_for(|| in_flight_jobs.len() == 2);
+
+  assert!(pool.drain(Duration::from_secs(5)));
+  assert_eq!(processed.load(Ordering::SeqCst), 2);
+  assert_eq!(pool.requeue_in_flight_jobs(), Vec::<u64>::new());
+
+  // the messages received while stopping are requeued without being processed
+  pool.process(get_delivery(3), channel.clone());
+  wait_for(|| !channel.requeued.lock().unwrap().is_empty());
+  assert_eq!(*channel.requeued.lock().unwrap(), vec![(3, None)]);
+  assert_eq!(processed.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn processor_pool_requeue_in_flight_jobs() {
+  let (control_sender, _control_receiver) = futures::channel::mpsc::unbounded();
+  let pool = ProcessorPool::with_processor(
+    2,
+    control_sender,
+    |delivery, _channel, cancellation_token: CancellationToken| {
+      // the first job ends quickly, the second one runs until it is cancelled
+      if delivery.delivery_tag == 1 {
+        return Ok(());
+      }
+      wait_for(|| cancellation_token.is_cancelled());
+      Ok(())
+    },
+  );
+
+  let channel = TestChannel::default();
+  let in_flight_jobs = pool.get_in_flight_jobs();
+  pool.process(get_delivery(1), channel.clone());
+  pool.process(get_delivery(2), channel.clone());
+  wait_for(|| in_flight_jobs.len() == 1);
+
+  assert!(!in_flight_jobs.cancel(100));
+  assert!(!pool.drain(Duration::from_millis(200)));
+  assert_eq!(pool.requeue_in_flight_jobs(), vec![2]);
+  assert_eq!(*channel.requeued.lock().unwrap(), vec![(2, Some(200))]);
+  assert_eq!(in_flight_jobs.len(), 0);
+}
+
+#[test]
+fn processor_pool_abandon_in_flight_jobs() {
+  let processed = Arc::new(Mutex::new(vec![]));
+
+  let (control_sender, _control_receiver) = futures::channel::mpsc::unbounded();
+  let pool = {
+    let processed = processed.clone();
+    ProcessorPool::with_processor(
+      1,
+      control_sender,
+      move |delivery, _channel, cancellation_token: CancellationToken| {
+        processed.lock().unwrap().push(delivery.delivery_tag);
+        // the first job runs until it is cancelled
+        if delivery.delivery_tag == 1 {
+          wait_for(|| cancellation_token.is_cancelled());
+        }
+        Ok(())
+      },
+    )
+  };
+
+  let channel = TestChannel::default();
+  let in_flight_jobs = pool.get_in_flight_jobs();
+  pool.process(get_delivery(1), channel.clone());
+  pool.process(get_delivery(2), channel.clone());
+  wait_for(|| in_flight_jobs.len() == 1);
+
+  // the connection is lost: the running job and the pending message are given up
+  assert_eq!(pool.abandon_in_flight_jobs(), vec![1]);
+  assert_eq!(in_flight_jobs.len(), 0);
+
+  // the server delivers them again on the new connection, with other delivery tags
+  pool.process(get_delivery(3), channel.clone());
+  pool.process(get_delivery(4), channel.clone());
+  wait_for(|| processed.lock().unwrap().len() == 3);
+  thread::sleep(Duration::from_millis(100));
+
+  assert_eq!(*processed.lock().unwrap(), vec![1, 3, 4]);
+  assert!(channel.requeued.lock().unwrap().is_empty());
+}
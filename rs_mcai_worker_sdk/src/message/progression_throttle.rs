@@ -0,0 +1,238 @@
+use crate::{job::JobProgression, McaiChannel};
+use std::{
+  collections::HashMap,
+  sync::Mutex,
+  time::{Duration, Instant},
+};
+
+type PendingProgression<C> = (C, JobProgression);
+
+lazy_static! {
+  static ref PROGRESSION_THROTTLE: Mutex<ProgressionThrottle<Option<McaiChannel>>> =
+    Mutex::new(ProgressionThrottle::default());
+}
+
+/// Submit a progression, returns it if it must be published now
+///
+/// Otherwise it is kept as the pending progression of the job, replacing the previous one.
+pub fn submit(
+  channel: Option<McaiChannel>,
+  job_progression: JobProgression,
+  interval: Duration,
+) -> Option<PendingProgression<Option<McaiChannel>>> {
+  match PROGRESSION_THROTTLE.lock() {
+    Ok(mut throttle) => throttle.submit(channel, job_progression, interval, Instant::now()),
+    Err(_) => Some((channel, job_progression)),
+  }
+}
+
+/// Take the pending progressions which interval has ended
+pub fn take_due(interval: Duration) -> Vec<PendingProgression<Option<McaiChannel>>> {
+  PROGRESSION_THROTTLE
+    .lock()
+    .map(|mut throttle| throttle.take_due(interval, Instant::now()))
+    .unwrap_or_default()
+}
+
+/// Forget the publications of a finished job, returns its pending progression to publish
+pub fn release(job_id: u64) -> Option<PendingProgression<Option<McaiChannel>>> {
+  PROGRESSION_THROTTLE
+    .lock()
+    .ok()
+    .and_then(|mut throttle| throttle.release(job_id))
+}
+
+struct Publication<C> {
+  instant: Instant,
+  step_index: Option<u32>,
+  pending: Option<PendingProgression<C>>,
+}
+
+/// Coalesce the progressions published in tight loops
+///
+/// The first and the last progressions, as well as step changes, are always published.
+/// A skipped progression is kept until the next publication, the end of the interval
+/// or the end of the job, so the latest progression is never lost.
+struct ProgressionThrottle<C> {
+  publications: HashMap<u64, Publication<C>>,
+}
+
+impl<C> Default for ProgressionThrottle<C> {
+  fn default() -> Self {
+    ProgressionThrottle {
+      publications: HashMap::new(),
+    }
+  }
+}
+
+impl<C> ProgressionThrottle<C> {
+  fn submit(
+    &mut self,
+    channel: C,
+    job_progression: JobProgression,
+    interval: Duration,
+    now: Instant,
+  ) -> Option<PendingProgression<C>> {
+    let job_id = job_progression.get_job_id();
+    let progression = job_progression.get_progression();
+    let step_index = job_progression.get_step_index();
+
+    if progression >= 100 {
+      self.release(job_id);
+      return Some((channel, job_progression));
+    }
+
+    let publish = match self.publications.get(&job_id) {
+      None => true,
+      Some(_) if progression == 0 => true,
+      Some(publication) if publication.step_index != step_index => true,
+      Some(publication) => now.duration_since(publication.instant) >= interval,
+    };
+
+    if publish {
+      self.publications.insert(
+        job_id,
+        Publication {
+          instant: now,
+          step_index,
+          pending: None,
+        },
+      );
+      return Some((channel, job_progression));
+    }
+
+    if let Some(publication) = self.publications.get_mut(&job_id) {
+      publication.pending = Some((channel, job_progression));
+    }
+    None
+  }
+
+  fn take_due(&mut self, interval: Duration, now: Instant) -> Vec<PendingProgression<C>> {
+    self
+      .publications
+      .values_mut()
+      .filter(|publication| now.duration_since(publication.instant) >= interval)
+      .filter_map(|publication| {
+        let pending = publication.pending.take();
+        if pending.is_some() {
+          publication.instant = now;
+        }
+        pending
+      })
+      .collect()
+  }
+
+  fn release(&mut self, job_id: u64) -> Option<PendingProgression<C>> {
+    self
+      .publications
+      .remove(&job_id)
+      .and_then(|publication| publication.pending)
+  }
+}
+
+#[test]
+fn progression_throttle() {
+  let interval = Duration::from_millis(500);
+  let start = Instant::now();
+  let mut throttle = ProgressionThrottle::default();
+  let published = |publication: Option<PendingProgression<()>>| {
+    publication.map(|(_, job_progression)| job_progression.get_progression())
+  };
+
+  assert_eq!(
+    published(throttle.submit((), JobProgression::new(1, 0), interval, start)),
+    Some(0)
+  );
+  assert!(throttle
+    .submit((), JobProgression::new(1, 10), interval, start)
+    .is_none());
+  assert!(throttle
+    .submit(
+      (),
+      JobProgression::new(1, 20),
+      interval,
+      start + Duration::from_millis(499)
+    )
+    .is_none());
+  assert_eq!(
+    published(throttle.submit(
+      (),
+      JobProgression::new(1, 30),
+      interval,
+      start + Duration::from_millis(500)
+    )),
+    Some(30)
+  );
+
+  // another job is not throttled by the first one
+  assert_eq!(
+    published(throttle.submit(
+      (),
+      JobProgression::new(2, 10),
+      interval,
+      start + Duration::from_millis(500)
+    )),
+    Some(10)
+  );
+
+  // step changes are always published
+  assert!(throttle
+    .submit(
+      (),
+      JobProgression::new(1, 40).with_step("upload", 1, 2),
+      interval,
+      start + Duration::from_millis(600)
+    )
+    .is_some());
+  assert!(throttle
+    .submit(
+      (),
+      JobProgression::new(1, 50).with_step("upload", 1, 2),
+      interval,
+      start + Duration::from_millis(700)
+    )
+    .is_none());
+
+  // the skipped progression is published at the end of the interval
+  assert!(throttle
+    .take_due(interval, start + Duration::from_millis(1000))
+    .is_empty());
+  let due = throttle.take_due(interval, start + Duration::from_millis(1100));
+  assert_eq!(due.len(), 1);
+  assert_eq!(due[0].1.get_progression(), 50);
+  assert!(throttle
+    .take_due(interval, start + Duration::from_millis(2000))
+    .is_empty());
+
+  // or at the end of the job
+  assert!(throttle
+    .submit(
+      (),
+      JobProgression::new(1, 60).with_step("upload", 1, 2),
+      interval,
+      start + Duration::from_millis(1200)
+    )
+    .is_none());
+  assert_eq!(published(throttle.release(1)), Some(60));
+  assert!(!throttle.publications.contains_key(&1));
+
+  // the end of the job is always published, and releases the job
+  assert!(throttle
+    .submit(
+      (),
+      JobProgression::new(2, 20),
+      interval,
+      start + Duration::from_millis(600)
+    )
+    .is_none());
+  assert_eq!(
+    published(throttle.submit(
+      (),
+      JobProgression::new(2, 100),
+      interval,
+      start + Duration::from_millis(700)
+    )),
+    Some(100)
+  );
+  assert!(!throttle.publications.contains_key(&2));
+}
@@ -0,0 +1,55 @@
+use crate::{
+  job::{JobResult, JobStatus},
+  MessageError, Result,
+};
+use std::{
+  any::Any,
+  panic::{catch_unwind, AssertUnwindSafe},
+};
+
+/// Call a `MessageEvent` callback, converting its panic into a `ProcessingError`
+///
+/// The worker lock must be held around this call: a panic unwinding through a write guard
+/// would poison the worker for the next jobs.
+pub(crate) fn catch_panic<T, F: FnOnce() -> Result<T>>(job_id: u64, callback: F) -> Result<T> {
+  catch_unwind(AssertUnwindSafe(callback)).unwrap_or_else(|payload| {
+    let message = format!("Worker panicked: {}", get_panic_message(payload.as_ref()));
+    error!(target: &job_id.to_string(), "{}", message);
+
+    let job_result = JobResult::new(job_id)
+      .with_status(JobStatus::Error)
+      .with_message(&message);
+    Err(MessageError::ProcessingError(job_result))
+  })
+}
+
+fn get_panic_message(payload: &(dyn Any + Send)) -> &str {
+  if let Some(message) = payload.downcast_ref::<&str>() {
+    message
+  } else if let Some(message) = payload.downcast_ref::<String>() {
+    message
+  } else {
+    "unknown panic payload"
+  }
+}
+
+#[test]
+fn catch_panics() {
+  assert_eq!(catch_panic(1, || Ok(12)), Ok(12));
+
+  let result: Result<()> = catch_panic(1, || panic!("unexpected value {}", 12));
+  let expected = JobResult::new(1)
+    .with_status(JobStatus::Error)
+    .with_message("Worker panicked: unexpected value 12");
+  match result {
+    Err(MessageError::ProcessingError(job_result)) => {
+      assert_eq!(job_result.get_job_id(), 1);
+      assert_eq!(job_result.get_status(), &JobStatus::Error);
+      assert_eq!(job_result.get_parameters(), expected.get_parameters());
+    }
+    _ => panic!("expected a processing error"),
+  }
+
+  let result: Result<()> = catch_panic(1, || std::panic::panic_any(12));
+  assert!(result.is_err());
+}
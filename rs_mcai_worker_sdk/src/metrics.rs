@@ -0,0 +1,392 @@
+//! Activity of the worker, exposed in the Prometheus text format
+
+use crate::{
+  job::{JobResult, JobStatus},
+  MessageError, Result,
+};
+use std::{collections::BTreeMap, fmt::Write, sync::Mutex, time::Instant};
+
+/// Upper bounds of the job duration histogram buckets, in seconds
+static DURATION_BUCKETS: [f64; 12] = [
+  0.1, 0.5, 1.0, 5.0, 10.0, 30.0, 60.0, 300.0, 900.0, 1800.0, 3600.0, 7200.0,
+];
+
+lazy_static! {
+  static ref METRICS: Mutex<Metrics> = Mutex::new(Metrics::default());
+}
+
+#[derive(Default)]
+struct Metrics {
+  jobs_received: u64,
+  jobs_completed: u64,
+  jobs_stopped: u64,
+  jobs_errored: BTreeMap<&'static str, u64>,
+  jobs_requeued: BTreeMap<&'static str, u64>,
+  duration_buckets: [u64; 12],
+  duration_count: u64,
+  duration_sum: f64,
+  progressions: BTreeMap<u64, u8>,
+  reconnections: u64,
+  streams: BTreeMap<usize, StreamMetrics>,
+}
+
+struct StreamMetrics {
+  decoded_frames: u64,
+  job_frames: u64,
+  job_start: Instant,
+}
+
+fn update<F: FnOnce(&mut Metrics)>(function: F) {
+  if let Ok(mut metrics) = METRICS.lock() {
+    function(&mut metrics);
+  }
+}
+
+/// A job is received, its progression is reported until its result is published
+pub(crate) fn job_received(job_id: Option<u64>) {
+  update(|metrics| {
+    metrics.jobs_received += 1;
+    if let Some(job_id) = job_id {
+      metrics.progressions.insert(job_id, 0);
+    }
+  });
+}
+
+pub(crate) fn job_progression(job_id: u64, progression: u8) {
+  update(|metrics| {
+    if let Some(current) = metrics.progressions.get_mut(&job_id) {
+      *current = progression;
+    }
+  });
+}
+
+/// Count the published result of a job
+///
+/// An error is counted as requeued only if the job is `retried`, otherwise it is published in error.
+pub(crate) fn job_finished(
+  job_id: Option<u64>,
+  result: &Result<JobResult>,
+  stopped: bool,
+  retried: bool,
+) {
+  update(|metrics| {
+    if let Some(job_id) = job_id {
+      metrics.progressions.remove(&job_id);
+    }
+    metrics.count_result(result, stopped, retried);
+  });
+}
+
+pub(crate) fn reconnection() {
+  update(|metrics| metrics.reconnections += 1);
+}
+
+/// Reset the processing rate of the streams, at the beginning of a media job
+#[cfg(feature = "media")]
+pub(crate) fn media_job_started() {
+  update(|metrics| {
+    for stream in metrics.streams.values_mut() {
+      stream.job_frames = 0;
+      stream.job_start = Instant::now();
+    }
+  });
+}
+
+#[cfg(feature = "media")]
+pub(crate) fn frame_decoded(stream_index: usize) {
+  update(|metrics| {
+    let stream = metrics
+      .streams
+      .entry(stream_index)
+      .or_insert_with(|| StreamMetrics {
+        decoded_frames: 0,
+        job_frames: 0,
+        job_start: Instant::now(),
+      });
+    stream.decoded_frames += 1;
+    stream.job_frames += 1;
+  });
+}
+
+fn get_error_label(error: &MessageError) -> &'static str {
+  match error {
+    MessageError::RuntimeError(_) => "runtime_error",
+    MessageError::ParameterValueError(_) => "parameter_value_error",
+    MessageError::ProcessingError(_) => "processing_error",
+    MessageError::RequirementsError(_) => "requirements_error",
+    MessageError::NotImplemented() => "not_implemented",
+    MessageError::JobError(_) => "job_error",
+  }
+}
+
+impl Metrics {
+  fn count_result(&mut self, result: &Result<JobResult>, stopped: bool, retried: bool) {
+    let job_result = match result {
+      Ok(job_result) | Err(MessageError::ProcessingError(job_result)) => Some(job_result),
+      Err(_) => None,
+    };
+    if let Some(job_result) = job_result {
+      self.observe_duration(job_result.get_execution_duration());
+    }
+
+    if stopped {
+      self.jobs_stopped += 1;
+      return;
+    }
+
+    match result {
+      // a result in error is published on the job error queue, like a processing error
+      Ok(job_result) if job_result.get_status() == &JobStatus::Error => {
+        *self.jobs_errored.entry("processing_error").or_insert(0) += 1;
+      }
+      Ok(_) => self.jobs_completed += 1,
+      Err(error) => {
+        let counter = if retried {
+          &mut self.jobs_requeued
+        } else {
+          &mut self.jobs_errored
+        };
+        *counter.entry(get_error_label(error)).or_insert(0) += 1;
+      }
+    }
+  }
+
+  fn observe_duration(&mut self, duration: f64) {
+    for (bucket, upper_bound) in self
+      .duration_buckets
+      .iter_mut()
+      .zip(DURATION_BUCKETS.iter())
+    {
+      if duration <= *upper_bound {
+        *bucket += 1;
+      }
+    }
+    self.duration_count += 1;
+    self.duration_sum += duration;
+  }
+
+  fn render(&self) -> String {
+    let mut output = String::new();
+
+    write_metric(
+      &mut output,
+      "mcai_jobs_received_total",
+      "counter",
+      "Number of received jobs",
+      &[(String::new(), self.jobs_received as f64)],
+    );
+    write_metric(
+      &mut output,
+      "mcai_jobs_completed_total",
+      "counter",
+      "Number of completed jobs",
+      &[(String::new(), self.jobs_completed as f64)],
+    );
+    write_metric(
+      &mut output,
+      "mcai_jobs_stopped_total",
+      "counter",
+      "Number of stopped jobs",
+      &[(String::new(), self.jobs_stopped as f64)],
+    );
+    write_metric(
+      &mut output,
+      "mcai_jobs_errored_total",
+      "counter",
+      "Number of jobs published in error, by error",
+      &labelled(
+        self
+          .jobs_errored
+          .iter()
+          .map(|(error, count)| (error, *count as f64)),
+        "error",
+      ),
+    );
+    write_metric(
+      &mut output,
+      "mcai_jobs_requeued_total",
+      "counter",
+      "Number of jobs rejected to be retried, by error",
+      &labelled(
+        self
+          .jobs_requeued
+          .iter()
+          .map(|(error, count)| (error, *count as f64)),
+        "error",
+      ),
+    );
+
+    let mut buckets: Vec<(String, f64)> = DURATION_BUCKETS
+      .iter()
+      .zip(self.duration_buckets.iter())
+      .map(|(upper_bound, count)| (format!("{{le=\"{}\"}}", upper_bound), *count as f64))
+      .collect();
+    buckets.push(("{le=\"+Inf\"}".to_string(), self.duration_count as f64));
+    write_metric(
+      &mut output,
+      "mcai_job_duration_seconds",
+      "histogram",
+      "Execution duration of the jobs",
+      &[],
+    );
+    write_samples(&mut output, "mcai_job_duration_seconds_bucket", &buckets);
+    write_samples(
+      &mut output,
+      "mcai_job_duration_seconds_sum",
+      &[(String::new(), self.duration_sum)],
+    );
+    write_samples(
+      &mut output,
+      "mcai_job_duration_seconds_count",
+      &[(String::new(), self.duration_count as f64)],
+    );
+
+    let current_jobs: Vec<(String, f64)> = self
+      .progressions
+      .keys()
+      .map(|job_id| (format!("{{job_id=\"{}\"}}", job_id), 1.0))
+      .collect();
+    write_metric(
+      &mut output,
+      "mcai_current_job",
+      "gauge",
+      "Jobs currently processed by the worker",
+      &current_jobs,
+    );
+    write_metric(
+      &mut output,
+      "mcai_job_progression_percent",
+      "gauge",
+      "Progression of the jobs currently processed by the worker",
+      &labelled(
+        self
+          .progressions
+          .iter()
+          .map(|(job_id, progression)| (job_id, *progression as f64)),
+        "job_id",
+      ),
+    );
+    write_metric(
+      &mut output,
+      "mcai_amqp_reconnections_total",
+      "counter",
+      "Number of reconnections to the AMQP server",
+      &[(String::new(), self.reconnections as f64)],
+    );
+
+    write_metric(
+      &mut output,
+      "mcai_decoded_frames_total",
+      "counter",
+      "Number of frames processed by the media worker, by stream",
+      &labelled(
+        self
+          .streams
+          .iter()
+          .map(|(index, stream)| (index, stream.decoded_frames as f64)),
+        "stream",
+      ),
+    );
+
+    let processing_rates = self.streams.iter().map(|(index, stream)| {
+      let elapsed = stream.job_start.elapsed().as_secs_f64();
+      let fps = if elapsed > 0.0 {
+        stream.job_frames as f64 / elapsed
+      } else {
+        0.0
+      };
+      (index, fps)
+    });
+    write_metric(
+      &mut output,
+      "mcai_processing_fps",
+      "gauge",
+      "Frames processed per second during the current media job, by stream",
+      &labelled(processing_rates, "stream"),
+    );
+
+    output
+  }
+}
+
+fn labelled<K: ToString, I: Iterator<Item = (K, f64)>>(
+  values: I,
+  label: &str,
+) -> Vec<(String, f64)> {
+  values
+    .map(|(key, value)| (format!("{{{}=\"{}\"}}", label, key.to_string()), value))
+    .collect()
+}
+
+fn write_metric(
+  output: &mut String,
+  name: &str,
+  kind: &str,
+  help: &str,
+  samples: &[(String, f64)],
+) {
+  let _ = writeln!(output, "# HELP {} {}", name, help);
+  let _ = writeln!(output, "# TYPE {} {}", name, kind);
+  write_samples(output, name, samples);
+}
+
+fn write_samples(output: &mut String, name: &str, samples: &[(String, f64)]) {
+  for (labels, value) in samples {
+    let _ = writeln!(output, "{}{} {}", name, labels, value);
+  }
+}
+
+/// Metrics of the worker in the Prometheus text format
+pub(crate) fn render() -> String {
+  METRICS
+    .lock()
+    .map(|metrics| metrics.render())
+    .unwrap_or_default()
+}
+
+#[test]
+fn metrics_rendering() {
+  let mut metrics = Metrics {
+    jobs_received: 3,
+    jobs_completed: 1,
+    ..Default::default()
+  };
+  metrics.jobs_errored.insert("processing_error", 2);
+  metrics.observe_duration(0.3);
+  metrics.observe_duration(42.0);
+  metrics.progressions.insert(123, 42);
+
+  let output = metrics.render();
+  assert!(output.contains("# TYPE mcai_jobs_received_total counter\nmcai_jobs_received_total 3\n"));
+  assert!(output.contains("mcai_jobs_completed_total 1\n"));
+  assert!(output.contains("mcai_jobs_errored_total{error=\"processing_error\"} 2\n"));
+  assert!(output.contains("mcai_job_duration_seconds_bucket{le=\"0.1\"} 0\n"));
+  assert!(output.contains("mcai_job_duration_seconds_bucket{le=\"0.5\"} 1\n"));
+  assert!(output.contains("mcai_job_duration_seconds_bucket{le=\"60\"} 2\n"));
+  assert!(output.contains("mcai_job_duration_seconds_bucket{le=\"+Inf\"} 2\n"));
+  assert!(output.contains("mcai_job_duration_seconds_sum 42.3\n"));
+  assert!(output.contains("mcai_job_duration_seconds_count 2\n"));
+  assert!(output.contains("mcai_current_job{job_id=\"123\"} 1\n"));
+  assert!(output.contains("mcai_job_progression_percent{job_id=\"123\"} 42\n"));
+}
+
+#[test]
+fn metrics_results() {
+  use crate::JobError;
+
+  let mut metrics = Metrics::default();
+  let transient = Err(JobError::transient("unavailable").into());
+  metrics.count_result(&transient, false, true);
+  // published in error once the maximum number of retries is reached
+  metrics.count_result(&transient, false, false);
+  metrics.count_result(&Ok(JobResult::new(1)), false, false);
+  metrics.count_result(&Ok(JobResult::new(2)), true, false);
+  let error_result = Ok(JobResult::new(3).with_status(JobStatus::Error));
+  metrics.count_result(&error_result, false, false);
+
+  assert_eq!(metrics.jobs_requeued.values().sum::<u64>(), 1);
+  assert_eq!(metrics.jobs_errored.values().sum::<u64>(), 2);
+  assert_eq!(metrics.jobs_errored.get("processing_error"), Some(&1));
+  assert_eq!(metrics.jobs_completed, 1);
+  assert_eq!(metrics.jobs_stopped, 1);
+}
@@ -1,11 +1,22 @@
 pub mod container;
 pub mod media_segment;
+pub mod requirement;
 pub mod store;
+pub mod template;
+pub mod validation;
 
 use crate::{MessageError, Result};
+use chrono::{DateTime, Utc};
 pub use media_segment::MediaSegments;
+pub use requirement::Requirement;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::Value;
+use std::{collections::HashMap, convert::TryFrom, fmt, time::Duration};
+use url::Url;
+pub use validation::ParameterViolation;
+
+/// Displayed instead of the values of the secret parameters
+pub static REDACTED_VALUE: &str = "[REDACTED]";
 
 pub trait ParameterValue {
   fn parse_value(content: Value, store: &Option<String>) -> Result<Self>
@@ -19,14 +30,23 @@ pub trait ParameterValue {
         store_code
       );
 
-      if let Value::String(credential_key) = content {
+      let content = if let Value::String(credential_key) = content {
         Self::from_store(&credential_key, &store_code)
       } else {
         Err(MessageError::ParameterValueError(format!(
           "Cannot handle credential type for {:?}",
           content
         )))
-      }?
+      }?;
+
+      // the value retrieved from the store is never part of the error
+      return Self::from_value(content).map_err(|error| match error {
+        MessageError::ParameterValueError(_) => MessageError::ParameterValueError(format!(
+          "Cannot parse the value retrieved from store {}",
+          store_code
+        )),
+        error => error,
+      });
     } else {
       content
     };
@@ -134,12 +154,154 @@ impl ParameterValue for bool {
   }
 }
 
+macro_rules! impl_integer_parameter_value {
+  ($($integer: ty),*) => {
+    $(
+      impl ParameterValue for $integer {
+        fn from_value(value: Value) -> Result<$integer> {
+          match value {
+            Value::String(value) => value
+              .parse()
+              .map_err(|e| MessageError::ParameterValueError(format!("{:?}", e))),
+            Value::Number(value) => value
+              .as_u64()
+              .and_then(|value| <$integer>::try_from(value).ok())
+              .or_else(|| value.as_i64().and_then(|value| <$integer>::try_from(value).ok()))
+              .ok_or_else(|| {
+                MessageError::ParameterValueError(format!(
+                  "Cannot convert value type '{:?}' to type {}",
+                  value,
+                  std::any::type_name::<Self>()
+                ))
+              }),
+            _ => Err(MessageError::ParameterValueError(format!(
+              "Cannot convert value type '{:?}' to type {}",
+              value,
+              std::any::type_name::<Self>()
+            ))),
+          }
+        }
+
+        fn get_type_as_string() -> String {
+          "integer".to_string()
+        }
+      }
+    )*
+  };
+}
+
+impl_integer_parameter_value!(i8, i16, i32, u8, u16, u32, u64);
+
+impl ParameterValue for f32 {
+  fn from_value(value: Value) -> Result<f32> {
+    f64::from_value(value).map(|value| value as f32)
+  }
+
+  fn get_type_as_string() -> String {
+    "float".to_string()
+  }
+}
+
 impl ParameterValue for Vec<String> {
   fn get_type_as_string() -> String {
     "array_of_strings".to_string()
   }
 }
 
+impl ParameterValue for Vec<i64> {
+  fn get_type_as_string() -> String {
+    "array_of_integers".to_string()
+  }
+}
+
+impl ParameterValue for Vec<f64> {
+  fn get_type_as_string() -> String {
+    "array_of_floats".to_string()
+  }
+}
+
+impl ParameterValue for Vec<bool> {
+  fn get_type_as_string() -> String {
+    "array_of_booleans".to_string()
+  }
+}
+
+impl ParameterValue for HashMap<String, Value> {
+  fn get_type_as_string() -> String {
+    "object".to_string()
+  }
+}
+
+impl ParameterValue for Value {
+  fn get_type_as_string() -> String {
+    "json".to_string()
+  }
+}
+
+/// Date and time in the RFC 3339 format, like `2020-10-17T09:30:00Z`
+impl ParameterValue for DateTime<Utc> {
+  fn get_type_as_string() -> String {
+    "datetime".to_string()
+  }
+}
+
+/// Duration in seconds, or with a unit like `500ms`, `30s`, `5m` or `2h`
+impl ParameterValue for Duration {
+  fn from_value(value: Value) -> Result<Duration> {
+    let seconds = match &value {
+      Value::Number(seconds) => seconds.as_f64(),
+      Value::String(duration) => {
+        let duration = duration.trim();
+        let (number, unit) = duration.split_at(
+          duration
+            .find(|character: char| character.is_alphabetic())
+            .unwrap_or(duration.len()),
+        );
+        let factor = match unit.trim() {
+          "ms" => Some(0.001),
+          "" | "s" => Some(1.0),
+          "m" => Some(60.0),
+          "h" => Some(3600.0),
+          _ => None,
+        };
+        factor.and_then(|factor| {
+          number
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .map(|number| number * factor)
+        })
+      }
+      Value::Object(_) => {
+        return serde_json::value::from_value(value)
+          .map_err(|e| MessageError::ParameterValueError(format!("{:?}", e)))
+      }
+      _ => None,
+    };
+
+    // negative, infinite or overflowing durations are rejected
+    seconds
+      .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+      .ok_or_else(|| {
+        MessageError::ParameterValueError(format!(
+          "Cannot convert value '{:?}' to type {}",
+          value,
+          std::any::type_name::<Self>()
+        ))
+      })
+  }
+
+  fn get_type_as_string() -> String {
+    "duration".to_string()
+  }
+}
+
+impl ParameterValue for Url {
+  fn get_type_as_string() -> String {
+    "url".to_string()
+  }
+}
+
 impl ParameterValue for Requirement {
   fn get_type_as_string() -> String {
     "requirements".to_string()
@@ -152,12 +314,7 @@ impl ParameterValue for MediaSegments {
   }
 }
 
-#[derive(Clone, Default, Debug, Serialize, Deserialize, PartialEq)]
-pub struct Requirement {
-  pub paths: Option<Vec<String>>,
-}
-
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Clone, Serialize, Deserialize, PartialEq)]
 pub struct Parameter {
   pub id: String,
   #[serde(rename = "type")]
@@ -165,6 +322,9 @@ pub struct Parameter {
   pub store: Option<String>,
   pub value: Option<Value>,
   pub default: Option<Value>,
+  /// Whether the placeholders of the value are resolved, see the [template](template/index.html) module
+  #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+  pub template: bool,
 }
 
 impl Parameter {
@@ -175,6 +335,71 @@ impl Parameter {
   pub fn has_value_or_default(&self) -> bool {
     self.value.is_some() || self.default.is_some()
   }
+
+  /// Parameters fetched from a store, or typed `credential`, are masked in the logs and the results
+  pub fn is_secret(&self) -> bool {
+    self.store.is_some() || self.kind == "credential"
+  }
+
+  /// Returns a copy of the parameter, with the value masked if it is secret
+  pub fn redacted(&self) -> Self {
+    if !self.is_secret() {
+      return self.clone();
+    }
+
+    let redact = |value: &Option<Value>| {
+      value
+        .as_ref()
+        .map(|_| Value::String(REDACTED_VALUE.to_string()))
+    };
+
+    Parameter {
+      value: redact(&self.value),
+      default: redact(&self.default),
+      ..self.clone()
+    }
+  }
+}
+
+impl fmt::Debug for Parameter {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let parameter = self.redacted();
+
+    f.debug_struct("Parameter")
+      .field("id", &parameter.id)
+      .field("kind", &parameter.kind)
+      .field("store", &parameter.store)
+      .field("value", &parameter.value)
+      .field("default", &parameter.default)
+      .field("template", &parameter.template)
+      .finish()
+  }
+}
+
+/// Mask the secret values in a message
+pub(crate) fn redact(message: &str, secrets: &[&Value]) -> String {
+  let mut message = message.to_string();
+  for secret in secrets {
+    match secret {
+      Value::String(secret) if !secret.is_empty() => {
+        message = message.replace(secret.as_str(), REDACTED_VALUE);
+      }
+      // as displayed by the serde errors, and by the debug format of the JSON values
+      Value::Number(_) | Value::Bool(_) => {
+        for representation in &[format!("`{}`", secret), format!("{:?}", secret)] {
+          message = message.replace(representation.as_str(), REDACTED_VALUE);
+        }
+      }
+      Value::Array(values) => {
+        message = redact(&message, &values.iter().collect::<Vec<&Value>>());
+      }
+      Value::Object(values) => {
+        message = redact(&message, &values.values().collect::<Vec<&Value>>());
+      }
+      _ => {}
+    }
+  }
+  message
 }
 
 impl ToString for Parameter {
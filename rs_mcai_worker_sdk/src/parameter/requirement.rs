@@ -0,0 +1,320 @@
+//! Requirements of a job on the worker instance
+//!
+//! A job with unmet requirements is rejected, to be processed by another instance.
+
+use crate::{MessageError, Result};
+use std::{
+  cmp, env, fs,
+  net::{TcpStream, ToSocketAddrs},
+  path::{Path, PathBuf},
+  time::Duration,
+};
+use sysinfo::{DiskExt, RefreshKind, System, SystemExt};
+
+static ENDPOINT_CONNECTION_TIMEOUT: Duration = Duration::from_secs(5);
+/// Mount point of the cgroup of the process, in a container
+static CGROUP_DIRECTORY: &str = "/sys/fs/cgroup";
+
+#[derive(Clone, Default, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Requirement {
+  /// Files or directories that must exist
+  pub paths: Option<Vec<String>>,
+  /// Minimum free space on the file system of some paths, which may not exist yet
+  pub disk_space: Option<Vec<DiskSpaceRequirement>>,
+  /// Minimum available memory, in bytes
+  ///
+  /// It is the available memory of the host, read from `/proc/meminfo` on Linux,
+  /// or the memory left before the limit of the cgroup of a container if it is lower.
+  pub memory: Option<u64>,
+  /// Executables that must be found in the `PATH`
+  pub executables: Option<Vec<String>>,
+  /// Environment variables that must be set
+  pub environment_variables: Option<Vec<String>>,
+  /// TCP endpoints that must accept connections, like `database:5432`
+  pub endpoints: Option<Vec<String>>,
+}
+
+#[derive(Clone, Default, Debug, Serialize, Deserialize, PartialEq)]
+pub struct DiskSpaceRequirement {
+  pub path: String,
+  /// Minimum free space, in bytes
+  pub minimum: u64,
+}
+
+impl Requirement {
+  /// Returns a `RequirementsError` describing the first unmet requirement
+  pub fn check(&self) -> Result<()> {
+    for path in self.paths.iter().flatten() {
+      let p = Path::new(path);
+      if !p.exists() {
+        return Err(MessageError::RequirementsError(format!(
+          "Warning: Required file does not exists: {:?}",
+          p
+        )));
+      }
+    }
+
+    for variable in self.environment_variables.iter().flatten() {
+      if env::var_os(variable).is_none() {
+        return Err(MessageError::RequirementsError(format!(
+          "Required environment variable is not set: {}",
+          variable
+        )));
+      }
+    }
+
+    for executable in self.executables.iter().flatten() {
+      if find_executable(executable).is_none() {
+        return Err(MessageError::RequirementsError(format!(
+          "Required executable not found in PATH: {}",
+          executable
+        )));
+      }
+    }
+
+    if let Some(memory) = self.memory {
+      let available_memory = get_available_memory();
+      if available_memory < memory {
+        return Err(MessageError::RequirementsError(format!(
+          "Not enough available memory: {} bytes required, {} bytes available",
+          memory, available_memory
+        )));
+      }
+    }
+
+    if let Some(disk_space) = &self.disk_space {
+      let mut system = System::new_with_specifics(RefreshKind::new().with_disks_list());
+      system.refresh_disks_list();
+
+      for requirement in disk_space {
+        let available_space = get_available_space(&system, &requirement.path).ok_or_else(|| {
+          MessageError::RequirementsError(format!(
+            "Unable to get the free disk space of {:?}",
+            requirement.path
+          ))
+        })?;
+
+        if available_space < requirement.minimum {
+          return Err(MessageError::RequirementsError(format!(
+            "Not enough free disk space on {:?}: {} bytes required, {} bytes available",
+            requirement.path, requirement.minimum, available_space
+          )));
+        }
+      }
+    }
+
+    for endpoint in self.endpoints.iter().flatten() {
+      check_endpoint(endpoint).map_err(|error| {
+        MessageError::RequirementsError(format!(
+          "Required endpoint {} is not reachable: {}",
+          endpoint, error
+        ))
+      })?;
+    }
+
+    Ok(())
+  }
+}
+
+fn find_executable(executable: &str) -> Option<PathBuf> {
+  if executable.contains(std::path::MAIN_SEPARATOR) {
+    let path = PathBuf::from(executable);
+    return Some(path).filter(|path| is_executable(path));
+  }
+
+  env::var_os("PATH").and_then(|paths| {
+    env::split_paths(&paths)
+      .map(|directory| directory.join(executable))
+      .find(|path| is_executable(path))
+  })
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+  use std::os::unix::fs::PermissionsExt;
+
+  path
+    .metadata()
+    .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+    .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+  path.is_file()
+}
+
+/// Available memory of the host, bounded by the memory left in the cgroup of the process
+fn get_available_memory() -> u64 {
+  let mut system = System::new_with_specifics(RefreshKind::new().with_memory());
+  system.refresh_memory();
+  let available_memory = system.get_available_memory() * 1024;
+
+  match get_cgroup_available_memory(Path::new(CGROUP_DIRECTORY)) {
+    Some(cgroup_available_memory) => cmp::min(available_memory, cgroup_available_memory),
+    None => available_memory,
+  }
+}
+
+/// Memory left before the limit of the cgroup, `None` if the memory of the cgroup is not limited
+///
+/// The limit is read from `memory.max` with cgroup v2, or from `memory/memory.limit_in_bytes`
+/// with cgroup v1, the memory used by the cgroup from `memory.current` or `memory/memory.usage_in_bytes`.
+fn get_cgroup_available_memory(cgroup_directory: &Path) -> Option<u64> {
+  let read_value = |file: &str| fs::read_to_string(cgroup_directory.join(file)).ok();
+  let parse_value = |content: String| content.trim().parse::<u64>().ok();
+
+  let (limit, usage) = match read_value("memory.max") {
+    // the limit is `max` when the memory is not limited
+    Some(limit) => (parse_value(limit)?, read_value("memory.current")),
+    None => (
+      parse_value(read_value("memory/memory.limit_in_bytes")?)?,
+      read_value("memory/memory.usage_in_bytes"),
+    ),
+  };
+
+  let usage = usage.and_then(parse_value).unwrap_or(0);
+  Some(limit.saturating_sub(usage))
+}
+
+/// Free space of the disk with the longest mount point containing the path
+///
+/// A path that does not exist yet, like an output directory, is on the disk of its nearest existing ancestor.
+fn get_available_space(system: &System, path: &str) -> Option<u64> {
+  let path = env::current_dir().ok()?.join(path);
+  let path = path
+    .ancestors()
+    .find_map(|ancestor| ancestor.canonicalize().ok())?;
+
+  system
+    .get_disks()
+    .iter()
+    .filter(|disk| path.starts_with(disk.get_mount_point()))
+    .max_by_key(|disk| disk.get_mount_point().as_os_str().len())
+    .map(|disk| disk.get_available_space())
+}
+
+fn check_endpoint(endpoint: &str) -> std::result::Result<(), String> {
+  let addresses = endpoint
+    .to_socket_addrs()
+    .map_err(|error| error.to_string())?;
+
+  let mut last_error = format!("unable to resolve {}", endpoint);
+  for address in addresses {
+    match TcpStream::connect_timeout(&address, ENDPOINT_CONNECTION_TIMEOUT) {
+      Ok(_) => return Ok(()),
+      Err(error) => last_error = error.to_string(),
+    }
+  }
+  Err(last_error)
+}
+
+#[test]
+fn requirements_check() {
+  assert!(Requirement::default().check().is_ok());
+
+  let requirement = Requirement {
+    paths: Some(vec!["./Cargo.toml".to_string()]),
+    environment_variables: Some(vec!["PATH".to_string()]),
+    executables: Some(vec!["sh".to_string()]),
+    memory: Some(1),
+    ..Default::default()
+  };
+  assert!(requirement.check().is_ok());
+
+  let requirement = Requirement {
+    environment_variables: Some(vec!["REQUIREMENT_TEST_UNDEFINED".to_string()]),
+    ..Default::default()
+  };
+  assert_eq!(
+    requirement.check(),
+    Err(MessageError::RequirementsError(
+      "Required environment variable is not set: REQUIREMENT_TEST_UNDEFINED".to_string()
+    ))
+  );
+
+  let requirement = Requirement {
+    executables: Some(vec!["requirement_test_missing_executable".to_string()]),
+    ..Default::default()
+  };
+  assert_eq!(
+    requirement.check(),
+    Err(MessageError::RequirementsError(
+      "Required executable not found in PATH: requirement_test_missing_executable".to_string()
+    ))
+  );
+
+  let requirement = Requirement {
+    memory: Some(u64::MAX),
+    ..Default::default()
+  };
+  assert!(requirement.check().is_err());
+
+  // a missing path is on the disk of its nearest existing ancestor
+  let mut system = System::new_with_specifics(RefreshKind::new().with_disks_list());
+  system.refresh_disks_list();
+  assert_eq!(
+    get_available_space(&system, "./requirement/test/missing/path"),
+    get_available_space(&system, ".")
+  );
+  assert_eq!(
+    get_available_space(&system, "/requirement/test/missing/path"),
+    get_available_space(&system, "/")
+  );
+}
+
+#[test]
+fn requirements_cgroup_memory() {
+  let root = env::temp_dir().join(format!("mcai_cgroup_{}", std::process::id()));
+  let cgroup_v2 = root.join("v2");
+  let cgroup_v1 = root.join("v1");
+  fs::create_dir_all(&cgroup_v2).unwrap();
+  fs::create_dir_all(cgroup_v1.join("memory")).unwrap();
+
+  assert_eq!(get_cgroup_available_memory(&cgroup_v2), None);
+  assert_eq!(get_cgroup_available_memory(&cgroup_v1), None);
+
+  fs::write(cgroup_v2.join("memory.max"), "max\n").unwrap();
+  fs::write(cgroup_v2.join("memory.current"), "104857600\n").unwrap();
+  assert_eq!(get_cgroup_available_memory(&cgroup_v2), None);
+
+  fs::write(cgroup_v2.join("memory.max"), "536870912\n").unwrap();
+  assert_eq!(get_cgroup_available_memory(&cgroup_v2), Some(432013312));
+
+  fs::write(cgroup_v2.join("memory.current"), "600000000\n").unwrap();
+  assert_eq!(get_cgroup_available_memory(&cgroup_v2), Some(0));
+
+  let memory = cgroup_v1.join("memory");
+  fs::write(memory.join("memory.limit_in_bytes"), "536870912\n").unwrap();
+  fs::write(memory.join("memory.usage_in_bytes"), "36870912\n").unwrap();
+  assert_eq!(get_cgroup_available_memory(&cgroup_v1), Some(500000000));
+
+  fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn requirements_endpoints_check() {
+  use std::net::TcpListener;
+
+  let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+  let endpoint = listener.local_addr().unwrap().to_string();
+
+  let requirement = Requirement {
+    endpoints: Some(vec![endpoint.clone()]),
+    ..Default::default()
+  };
+  assert!(requirement.check().is_ok());
+
+  drop(listener);
+  let requirement = Requirement {
+    endpoints: Some(vec![endpoint]),
+    ..Default::default()
+  };
+  assert!(requirement.check().is_err());
+
+  let requirement = Requirement {
+    endpoints: Some(vec!["invalid endpoint".to_string()]),
+    ..Default::default()
+  };
+  assert!(requirement.check().is_err());
+}
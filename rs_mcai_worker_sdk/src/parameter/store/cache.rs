@@ -0,0 +1,59 @@
+use std::{
+  collections::HashMap,
+  time::{Duration, Instant},
+};
+
+/// Values kept for a time to live
+#[derive(Debug)]
+pub(crate) struct TtlCache<V: Clone> {
+  entries: HashMap<String, (V, Instant)>,
+}
+
+impl<V: Clone> Default for TtlCache<V> {
+  fn default() -> Self {
+    TtlCache {
+      entries: HashMap::new(),
+    }
+  }
+}
+
+impl<V: Clone> TtlCache<V> {
+  /// Get a value inserted less than `ttl` ago
+  pub fn get(&mut self, key: &str, ttl: Duration) -> Option<V> {
+    let expired = match self.entries.get(key) {
+      Some((value, inserted_at)) if inserted_at.elapsed() < ttl => return Some(value.clone()),
+      Some(_) => true,
+      None => false,
+    };
+
+    if expired {
+      self.entries.remove(key);
+    }
+    None
+  }
+
+  pub fn insert(&mut self, key: &str, value: V) {
+    self
+      .entries
+      .insert(key.to_string(), (value, Instant::now()));
+  }
+
+  pub fn remove(&mut self, key: &str) {
+    self.entries.remove(key);
+  }
+}
+
+#[test]
+fn ttl_cache() {
+  let mut cache = TtlCache::default();
+  assert_eq!(cache.get("key", Duration::from_secs(60)), None);
+
+  cache.insert("key", 42);
+  assert_eq!(cache.get("key", Duration::from_secs(60)), Some(42));
+  assert_eq!(cache.get("key", Duration::from_secs(0)), None);
+  assert_eq!(cache.get("key", Duration::from_secs(60)), None);
+
+  cache.insert("key", 42);
+  cache.remove("key");
+  assert_eq!(cache.get("key", Duration::from_secs(60)), None);
+}
@@ -0,0 +1,15 @@
+use super::{parse_value, CredentialStore};
+use serde_json::Value;
+use std::env::var;
+
+/// Read the credentials from the environment variables
+#[derive(Debug, Default)]
+pub struct EnvironmentStore {}
+
+impl CredentialStore for EnvironmentStore {
+  fn get_value(&self, credential_key: &str) -> Result<Value, String> {
+    var(credential_key)
+      .map_err(|error| error.to_string())
+      .map(parse_value)
+  }
+}
@@ -0,0 +1,52 @@
+use super::{parse_value, CredentialStore};
+use serde_json::Value;
+use std::{fs, path::PathBuf};
+
+/// Read the credentials from files named by their key, like mounted Docker or Kubernetes secrets
+#[derive(Debug)]
+pub struct FileStore {
+  directory: PathBuf,
+}
+
+impl FileStore {
+  pub fn new(directory: &str) -> Self {
+    FileStore {
+      directory: PathBuf::from(directory),
+    }
+  }
+}
+
+impl CredentialStore for FileStore {
+  fn get_value(&self, credential_key: &str) -> Result<Value, String> {
+    if credential_key.is_empty()
+      || credential_key.contains(&['/', '\\'][..])
+      || credential_key.starts_with('.')
+    {
+      return Err(format!("Invalid credential key: {:?}", credential_key));
+    }
+
+    let path = self.directory.join(credential_key);
+    let content = fs::read_to_string(&path)
+      .map_err(|error| format!("Unable to read credential {:?}: {}", path, error))?;
+
+    Ok(parse_value(
+      content.trim_end_matches(&['\r', '\n'][..]).to_string(),
+    ))
+  }
+}
+
+#[test]
+fn file_store() {
+  let directory = std::env::temp_dir().join(format!("mcai_secrets_{}", std::process::id()));
+  fs::create_dir_all(&directory).unwrap();
+  fs::write(directory.join("password"), "secret\n").unwrap();
+  fs::write(directory.join("ports"), "[80, 443]").unwrap();
+
+  let store = FileStore::new(directory.to_str().unwrap());
+  assert_eq!(store.get_value("password"), Ok(json!("secret")));
+  assert_eq!(store.get_value("ports"), Ok(json!([80, 443])));
+  assert!(store.get_value("missing").is_err());
+  assert!(store.get_value("../password").is_err());
+
+  fs::remove_dir_all(directory).unwrap();
+}
@@ -0,0 +1,74 @@
+//! Stores of the credentials referenced by the job parameters
+//!
+//! The store is selected by the `store` code of the parameter:
+//! `env` reads environment variables, `secret_file` reads mounted secrets (like `/run/secrets/<key>`),
+//! any other code is a StepFlow backend configured with `<STORE>_HOSTNAME`, `<STORE>_USERNAME` and `<STORE>_PASSWORD`.
+//! Codes like `file` or `secrets` keep selecting a backend, as in the orders written before the mounted secrets.
+//! Other stores can be registered with [`register_store`](fn.register_store.html).
+
+mod cache;
+mod environment;
+mod file;
+mod step_flow;
+
+pub use environment::EnvironmentStore;
+pub use file::FileStore;
+pub use step_flow::StepFlowStore;
+
+use crate::config::get_secrets_directory;
+use serde_json::Value;
+use std::{
+  collections::HashMap,
+  sync::{Arc, RwLock},
+};
+
+lazy_static! {
+  static ref STORES: RwLock<HashMap<String, Arc<dyn CredentialStore>>> =
+    RwLock::new(HashMap::new());
+}
+
+/// Backend providing the values of the credentials
+pub trait CredentialStore: Send + Sync {
+  fn get_value(&self, credential_key: &str) -> Result<Value, String>;
+}
+
+/// Use a custom store for the parameters with this store code
+pub fn register_store(store_code: &str, store: Arc<dyn CredentialStore>) {
+  if let Ok(mut stores) = STORES.write() {
+    stores.insert(store_code.to_string(), store);
+  }
+}
+
+/// Get the store of a store code, keeping it for the next requests
+pub fn get_store(store_code: &str) -> Arc<dyn CredentialStore> {
+  if let Some(store) = STORES
+    .read()
+    .ok()
+    .and_then(|stores| stores.get(store_code).cloned())
+  {
+    return store;
+  }
+
+  let store: Arc<dyn CredentialStore> = match store_code {
+    "env" | "ENV" | "environment" => Arc::new(EnvironmentStore::default()),
+    "secret_file" => Arc::new(FileStore::new(&get_secrets_directory())),
+    _ => Arc::new(StepFlowStore::new(store_code)),
+  };
+
+  if let Ok(mut stores) = STORES.write() {
+    stores
+      .entry(store_code.to_string())
+      .or_insert_with(|| store.clone());
+  }
+
+  store
+}
+
+pub fn request_value(credential_key: &str, store_code: &str) -> Result<Value, String> {
+  get_store(store_code).get_value(credential_key)
+}
+
+/// Credential values are JSON encoded, or raw strings
+fn parse_value(value: String) -> Value {
+  serde_json::from_str(&value).unwrap_or(Value::String(value))
+}
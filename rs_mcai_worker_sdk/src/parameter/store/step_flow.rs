@@ -0,0 +1,144 @@
+use super::{cache::TtlCache, parse_value, CredentialStore};
+use crate::{
+  config::*,
+  job::{Session, SessionBody, SessionResponseBody, ValueResponseBody},
+};
+use reqwest::{
+  blocking::{Client, Response},
+  header::{HeaderMap, HeaderValue, AUTHORIZATION},
+  StatusCode,
+};
+use serde_json::Value;
+use std::{sync::Mutex, time::Duration};
+
+/// Request the credentials to a StepFlow backend
+///
+/// The session tokens and the values are kept for `<STORE>_CACHE_TTL` seconds.
+#[derive(Debug)]
+pub struct StepFlowStore {
+  store_code: String,
+  sessions: Mutex<TtlCache<String>>,
+  values: Mutex<TtlCache<Value>>,
+}
+
+impl StepFlowStore {
+  pub fn new(store_code: &str) -> Self {
+    StepFlowStore {
+      store_code: store_code.to_string(),
+      sessions: Mutex::new(TtlCache::default()),
+      values: Mutex::new(TtlCache::default()),
+    }
+  }
+
+  /// Returns the access token, and whether it comes from the cache
+  fn get_access_token(
+    &self,
+    backend_endpoint: &str,
+    ttl: Duration,
+  ) -> Result<(String, bool), String> {
+    let backend_username = get_store_username(&self.store_code);
+    let session_key = format!("{}@{}", backend_username, backend_endpoint);
+
+    if let Some(access_token) = self
+      .sessions
+      .lock()
+      .ok()
+      .and_then(|mut sessions| sessions.get(&session_key, ttl))
+    {
+      return Ok((access_token, true));
+    }
+
+    let session_url = format!("{}/sessions", backend_endpoint);
+    let client = Client::builder().build().map_err(|e| format!("{:?}", e))?;
+
+    let session_body = SessionBody {
+      session: Session {
+        email: backend_username,
+        password: get_store_password(&self.store_code),
+      },
+    };
+
+    let response: SessionResponseBody = client
+      .post(&session_url)
+      .json(&session_body)
+      .send()
+      .map_err(|e| e.to_string())?
+      .json()
+      .map_err(|e| e.to_string())?;
+
+    if let Ok(mut sessions) = self.sessions.lock() {
+      sessions.insert(&session_key, response.access_token.clone());
+    }
+
+    Ok((response.access_token, false))
+  }
+
+  fn invalidate_access_token(&self, backend_endpoint: &str) {
+    let backend_username = get_store_username(&self.store_code);
+    let session_key = format!("{}@{}", backend_username, backend_endpoint);
+
+    if let Ok(mut sessions) = self.sessions.lock() {
+      sessions.remove(&session_key);
+    }
+  }
+
+  fn send_credential_request(
+    &self,
+    credential_url: &str,
+    access_token: &str,
+  ) -> Result<Response, String> {
+    let mut headers = HeaderMap::new();
+
+    headers.insert(
+      AUTHORIZATION,
+      HeaderValue::from_str(access_token).map_err(|e| format!("{:?}", e))?,
+    );
+
+    let client = Client::builder()
+      .default_headers(headers)
+      .build()
+      .map_err(|e| e.to_string())?;
+
+    client.get(credential_url).send().map_err(|e| e.to_string())
+  }
+}
+
+impl CredentialStore for StepFlowStore {
+  fn get_value(&self, credential_key: &str) -> Result<Value, String> {
+    let ttl = Duration::from_secs(get_store_cache_ttl(&self.store_code));
+    let backend_endpoint = get_store_hostname(&self.store_code);
+    let credential_url = format!("{}/credentials/{}", backend_endpoint, credential_key);
+
+    if let Some(value) = self
+      .values
+      .lock()
+      .ok()
+      .and_then(|mut values| values.get(&credential_url, ttl))
+    {
+      return Ok(value);
+    }
+
+    let (access_token, cached) = self.get_access_token(&backend_endpoint, ttl)?;
+    let mut response = self.send_credential_request(&credential_url, &access_token)?;
+
+    if cached && response.status() == StatusCode::UNAUTHORIZED {
+      debug!("Session expired on store {}, login again", self.store_code);
+      self.invalidate_access_token(&backend_endpoint);
+      let (access_token, _) = self.get_access_token(&backend_endpoint, ttl)?;
+      response = self.send_credential_request(&credential_url, &access_token)?;
+    }
+
+    let response: ValueResponseBody = response.json().map_err(|e| e.to_string())?;
+
+    let value = match response.data.value {
+      Value::String(string) => parse_value(string),
+      value => value,
+    };
+
+    if let Ok(mut values) = self.values.lock() {
+      values.insert(&credential_url, value.clone());
+    }
+
+    Ok(value)
+  }
+}
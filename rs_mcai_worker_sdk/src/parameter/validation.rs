@@ -0,0 +1,450 @@
+//! Validation of the job parameters against the JSON schema of the worker
+
+use super::{redact, REDACTED_VALUE};
+use regex::Regex;
+use schemars::schema::{InstanceType, RootSchema, Schema, SchemaObject, SingleOrVec};
+use serde_json::Value;
+use std::fmt;
+
+/// Invalid value of a job parameter
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ParameterViolation {
+  /// JSON pointer to the invalid value, like `/source_paths/1`
+  pub path: String,
+  pub message: String,
+}
+
+impl fmt::Display for ParameterViolation {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}: {}", self.path, self.message)
+  }
+}
+
+impl ParameterViolation {
+  fn new(path: &str, message: &str) -> Self {
+    ParameterViolation {
+      path: path.to_string(),
+      message: message.to_string(),
+    }
+  }
+}
+
+/// Check the parameters against the schema, returning all the violations
+///
+/// The values of the `secrets` parameters are masked in the messages, wherever they appear.
+pub fn validate(
+  schema: &RootSchema,
+  parameters: &Value,
+  secrets: &[String],
+) -> Vec<ParameterViolation> {
+  let validator = Validator {
+    root: schema,
+    secrets,
+  };
+  let mut violations = vec![];
+  validator.validate_object(&schema.schema, parameters, "", &mut violations);
+
+  let secret_values: Vec<&Value> = secrets
+    .iter()
+    .filter_map(|secret| parameters.get(secret))
+    .collect();
+  for violation in violations.iter_mut() {
+    violation.message = redact(&violation.message, &secret_values);
+  }
+  violations
+}
+
+struct Validator<'a> {
+  root: &'a RootSchema,
+  secrets: &'a [String],
+}
+
+impl<'a> Validator<'a> {
+  fn display(&self, value: &dyn ToString, path: &str) -> String {
+    let parameter = path.split('/').nth(1).unwrap_or_default();
+    if self.secrets.iter().any(|secret| secret == parameter) {
+      REDACTED_VALUE.to_string()
+    } else {
+      value.to_string()
+    }
+  }
+
+  fn validate(
+    &self,
+    schema: &Schema,
+    value: &Value,
+    path: &str,
+    violations: &mut Vec<ParameterViolation>,
+  ) {
+    match schema {
+      Schema::Bool(true) => {}
+      Schema::Bool(false) => violations.push(ParameterViolation::new(path, "not allowed")),
+      Schema::Object(schema_object) => self.validate_object(schema_object, value, path, violations),
+    }
+  }
+
+  fn is_valid(&self, schema: &Schema, value: &Value, path: &str) -> bool {
+    let mut violations = vec![];
+    self.validate(schema, value, path, &mut violations);
+    violations.is_empty()
+  }
+
+  fn validate_object(
+    &self,
+    schema: &SchemaObject,
+    value: &Value,
+    path: &str,
+    violations: &mut Vec<ParameterViolation>,
+  ) {
+    if let Some(reference) = &schema.reference {
+      let name = reference.trim_start_matches("#/definitions/");
+      match self.root.definitions.get(name) {
+        Some(definition) => self.validate(definition, value, path, violations),
+        None => violations.push(ParameterViolation::new(
+          path,
+          &format!("unknown schema reference {}", reference),
+        )),
+      }
+    }
+
+    if let Some(instance_type) = &schema.instance_type {
+      let allowed_types: Vec<&InstanceType> = match instance_type {
+        SingleOrVec::Single(instance_type) => vec![instance_type],
+        SingleOrVec::Vec(instance_types) => instance_types.iter().collect(),
+      };
+
+      if !allowed_types
+        .iter()
+        .any(|instance_type| is_instance_of(value, instance_type))
+      {
+        let expected: Vec<&str> = allowed_types
+          .iter()
+          .map(|instance_type| get_type_name(instance_type))
+          .collect();
+        violations.push(ParameterViolation::new(
+          path,
+          &format!(
+            "expected {}, found {}",
+            expected.join(" or "),
+            get_value_type_name(value)
+          ),
+        ));
+        return;
+      }
+    }
+
+    if let Some(enum_values) = &schema.enum_values {
+      if !enum_values.contains(value) {
+        let allowed: Vec<String> = enum_values.iter().map(|value| value.to_string()).collect();
+        violations.push(ParameterViolation::new(
+          path,
+          &format!(
+            "{} is not one of {}",
+            self.display(value, path),
+            allowed.join(", ")
+          ),
+        ));
+      }
+    }
+
+    if let Some(const_value) = &schema.const_value {
+      if const_value != value {
+        violations.push(ParameterViolation::new(
+          path,
+          &format!(
+            "expected {}, found {}",
+            const_value,
+            self.display(value, path)
+          ),
+        ));
+      }
+    }
+
+    if let Some(value) = value.as_f64() {
+      // the integer format gives the range of the type, like 0..=255 for `uint8`
+      let format_bounds = schema.format.as_deref().and_then(get_integer_format_bounds);
+      let number = schema.number.as_deref().cloned().unwrap_or_default();
+
+      let minimum = match (number.minimum, format_bounds) {
+        (Some(minimum), Some((format_minimum, _))) => Some(minimum.max(format_minimum)),
+        (minimum, format_bounds) => minimum.or_else(|| format_bounds.map(|bounds| bounds.0)),
+      };
+      let maximum = match (number.maximum, format_bounds) {
+        (Some(maximum), Some((_, format_maximum))) => Some(maximum.min(format_maximum)),
+        (maximum, format_bounds) => maximum.or_else(|| format_bounds.map(|bounds| bounds.1)),
+      };
+
+      if let Some(minimum) = minimum {
+        if value < minimum {
+          violations.push(ParameterViolation::new(
+            path,
+            &format!(
+              "{} is lower than the minimum {}",
+              self.display(&value, path),
+              minimum
+            ),
+          ));
+        }
+      }
+      if let Some(exclusive_minimum) = number.exclusive_minimum {
+        if value <= exclusive_minimum {
+          violations.push(ParameterViolation::new(
+            path,
+            &format!(
+              "{} must be greater than {}",
+              self.display(&value, path),
+              exclusive_minimum
+            ),
+          ));
+        }
+      }
+      if let Some(maximum) = maximum {
+        if value > maximum {
+          violations.push(ParameterViolation::new(
+            path,
+            &format!(
+              "{} is greater than the maximum {}",
+              self.display(&value, path),
+              maximum
+            ),
+          ));
+        }
+      }
+      if let Some(exclusive_maximum) = number.exclusive_maximum {
+        if value >= exclusive_maximum {
+          violations.push(ParameterViolation::new(
+            path,
+            &format!(
+              "{} must be lower than {}",
+              self.display(&value, path),
+              exclusive_maximum
+            ),
+          ));
+        }
+      }
+      if let Some(multiple_of) = number.multiple_of {
+        if (value / multiple_of).fract() != 0.0 {
+          violations.push(ParameterViolation::new(
+            path,
+            &format!(
+              "{} is not a multiple of {}",
+              self.display(&value, path),
+              multiple_of
+            ),
+          ));
+        }
+      }
+    }
+
+    if let (Some(string), Some(value)) = (&schema.string, value.as_str()) {
+      let length = value.chars().count() as u32;
+      if let Some(min_length) = string.min_length {
+        if length < min_length {
+          violations.push(ParameterViolation::new(
+            path,
+            &format!("must be at least {} characters long", min_length),
+          ));
+        }
+      }
+      if let Some(max_length) = string.max_length {
+        if length > max_length {
+          violations.push(ParameterViolation::new(
+            path,
+            &format!("must be at most {} characters long", max_length),
+          ));
+        }
+      }
+      if let Some(pattern) = &string.pattern {
+        match Regex::new(pattern) {
+          Ok(regex) if !regex.is_match(value) => violations.push(ParameterViolation::new(
+            path,
+            &format!("does not match the pattern {}", pattern),
+          )),
+          Ok(_) => {}
+          Err(error) => warn!(
+            "Invalid pattern {:?} in parameters schema: {}",
+            pattern, error
+          ),
+        }
+      }
+    }
+
+    if let (Some(array), Some(items)) = (&schema.array, value.as_array()) {
+      if let Some(min_items) = array.min_items {
+        if (items.len() as u32) < min_items {
+          violations.push(ParameterViolation::new(
+            path,
+            &format!("must contain at least {} items", min_items),
+          ));
+        }
+      }
+      if let Some(max_items) = array.max_items {
+        if (items.len() as u32) > max_items {
+          violations.push(ParameterViolation::new(
+            path,
+            &format!("must contain at most {} items", max_items),
+          ));
+        }
+      }
+      if array.unique_items == Some(true) {
+        for (index, item) in items.iter().enumerate() {
+          if items[..index].contains(item) {
+            violations.push(ParameterViolation::new(
+              &format!("{}/{}", path, index),
+              &format!("duplicated item {}", self.display(item, path)),
+            ));
+          }
+        }
+      }
+
+      match &array.items {
+        Some(SingleOrVec::Single(item_schema)) => {
+          for (index, item) in items.iter().enumerate() {
+            self.validate(
+              item_schema,
+              item,
+              &format!("{}/{}", path, index),
+              violations,
+            );
+          }
+        }
+        Some(SingleOrVec::Vec(item_schemas)) => {
+          for (index, item) in items.iter().enumerate() {
+            let item_path = format!("{}/{}", path, index);
+            if let Some(item_schema) = item_schemas.get(index) {
+              self.validate(item_schema, item, &item_path, violations);
+            } else if let Some(additional_items) = &array.additional_items {
+              self.validate(additional_items, item, &item_path, violations);
+            }
+          }
+        }
+        None => {}
+      }
+    }
+
+    if let (Some(object), Some(properties)) = (&schema.object, value.as_object()) {
+      for required in &object.required {
+        if !properties.contains_key(required) {
+          violations.push(ParameterViolation::new(
+            &format!("{}/{}", path, required),
+            "missing required parameter",
+          ));
+        }
+      }
+
+      for (key, property) in properties {
+        let property_path = format!("{}/{}", path, key);
+        if let Some(property_schema) = object.properties.get(key) {
+          self.validate(property_schema, property, &property_path, violations);
+        } else if let Some(additional_properties) = &object.additional_properties {
+          if let Schema::Bool(false) = additional_properties.as_ref() {
+            violations.push(ParameterViolation::new(
+              &property_path,
+              "unexpected parameter",
+            ));
+          } else {
+            self.validate(additional_properties, property, &property_path, violations);
+          }
+        }
+      }
+    }
+
+    if let Some(subschemas) = &schema.subschemas {
+      if let Some(all_of) = &subschemas.all_of {
+        for subschema in all_of {
+          self.validate(subschema, value, path, violations);
+        }
+      }
+
+      if let Some(any_of) = &subschemas.any_of {
+        if !any_of
+          .iter()
+          .any(|subschema| self.is_valid(subschema, value, path))
+        {
+          violations.push(ParameterViolation::new(
+            path,
+            "does not match any of the allowed values",
+          ));
+        }
+      }
+
+      if let Some(one_of) = &subschemas.one_of {
+        let matches = one_of
+          .iter()
+          .filter(|subschema| self.is_valid(subschema, value, path))
+          .count();
+        if matches != 1 {
+          violations.push(ParameterViolation::new(
+            path,
+            "does not match exactly one of the allowed values",
+          ));
+        }
+      }
+
+      if let Some(not) = &subschemas.not {
+        if self.is_valid(not, value, path) {
+          violations.push(ParameterViolation::new(path, "matches a forbidden value"));
+        }
+      }
+    }
+  }
+}
+
+/// Range of the integer formats generated by schemars
+fn get_integer_format_bounds(format: &str) -> Option<(f64, f64)> {
+  match format {
+    "int8" => Some((i8::MIN as f64, i8::MAX as f64)),
+    "int16" => Some((i16::MIN as f64, i16::MAX as f64)),
+    "int32" => Some((i32::MIN as f64, i32::MAX as f64)),
+    "int64" => Some((i64::MIN as f64, i64::MAX as f64)),
+    "int" => Some((isize::MIN as f64, isize::MAX as f64)),
+    "uint8" => Some((0.0, u8::MAX as f64)),
+    "uint16" => Some((0.0, u16::MAX as f64)),
+    "uint32" => Some((0.0, u32::MAX as f64)),
+    "uint64" => Some((0.0, u64::MAX as f64)),
+    "uint" => Some((0.0, usize::MAX as f64)),
+    _ => None,
+  }
+}
+
+fn is_instance_of(value: &Value, instance_type: &InstanceType) -> bool {
+  match instance_type {
+    InstanceType::Null => value.is_null(),
+    InstanceType::Boolean => value.is_boolean(),
+    InstanceType::Object => value.is_object(),
+    InstanceType::Array => value.is_array(),
+    InstanceType::Number => value.is_number(),
+    InstanceType::String => value.is_string(),
+    InstanceType::Integer => {
+      value.is_i64()
+        || value.is_u64()
+        || value
+          .as_f64()
+          .map(|value| value.fract() == 0.0)
+          .unwrap_or(false)
+    }
+  }
+}
+
+fn get_type_name(instance_type: &InstanceType) -> &'static str {
+  match instance_type {
+    InstanceType::Null => "null",
+    InstanceType::Boolean => "boolean",
+    InstanceType::Object => "object",
+    InstanceType::Array => "array",
+    InstanceType::Number => "number",
+    InstanceType::String => "string",
+    InstanceType::Integer => "integer",
+  }
+}
+
+fn get_value_type_name(value: &Value) -> &'static str {
+  match value {
+    Value::Null => "null",
+    Value::Bool(_) => "boolean",
+    Value::Number(_) => "number",
+    Value::String(_) => "string",
+    Value::Array(_) => "array",
+    Value::Object(_) => "object",
+  }
+}
@@ -0,0 +1,90 @@
+use rand::Rng;
+use std::time::Duration;
+
+/// Delays between the attempts to reconnect to the AMQP server
+///
+/// The delay doubles after each failed attempt, up to the maximum delay.
+/// A random jitter spreads the reconnections of the worker instances.
+pub struct ReconnectionBackoff {
+  initial_delay: Duration,
+  max_delay: Duration,
+  max_attempts: Option<u32>,
+  attempts: u32,
+}
+
+impl ReconnectionBackoff {
+  pub fn new(initial_delay: Duration, max_delay: Duration, max_attempts: Option<u32>) -> Self {
+    ReconnectionBackoff {
+      initial_delay,
+      max_delay,
+      max_attempts,
+      attempts: 0,
+    }
+  }
+
+  pub fn get_attempts(&self) -> u32 {
+    self.attempts
+  }
+
+  /// Restart the backoff once the worker consumes the jobs again
+  pub fn reset(&mut self) {
+    self.attempts = 0;
+  }
+
+  /// Returns the delay before the next attempt, or `None` when no attempt remains
+  pub fn next_delay(&mut self) -> Option<Duration> {
+    if let Some(max_attempts) = self.max_attempts {
+      if self.attempts >= max_attempts {
+        return None;
+      }
+    }
+
+    let delay = self.get_exponential_delay();
+    self.attempts += 1;
+
+    let delay_ms = delay.as_millis() as u64;
+    let jitter_ms = rand::thread_rng().gen_range(0, delay_ms / 2 + 1);
+    Some(Duration::from_millis(delay_ms - jitter_ms))
+  }
+
+  fn get_exponential_delay(&self) -> Duration {
+    let factor = 2u32.checked_pow(self.attempts).unwrap_or(u32::MAX);
+    self
+      .initial_delay
+      .checked_mul(factor)
+      .map(|delay| std::cmp::min(delay, self.max_delay))
+      .unwrap_or(self.max_delay)
+  }
+}
+
+#[test]
+pub fn test_reconnection_backoff() {
+  let mut backoff = ReconnectionBackoff::new(
+    Duration::from_millis(1000),
+    Duration::from_millis(5000),
+    Some(5),
+  );
+
+  let expected_delays = [1000, 2000, 4000, 5000, 5000];
+  for expected_delay in expected_delays.iter() {
+    let delay = backoff.next_delay().unwrap().as_millis() as u64;
+    assert!(delay <= *expected_delay);
+    assert!(delay >= *expected_delay / 2);
+  }
+  assert_eq!(backoff.get_attempts(), 5);
+  assert!(backoff.next_delay().is_none());
+
+  backoff.reset();
+  assert!(backoff.next_delay().unwrap() <= Duration::from_millis(1000));
+}
+
+#[test]
+pub fn test_unlimited_reconnection_backoff() {
+  let mut backoff =
+    ReconnectionBackoff::new(Duration::from_millis(100), Duration::from_secs(60), None);
+
+  for _ in 0..100 {
+    assert!(backoff.next_delay().unwrap() <= Duration::from_secs(60));
+  }
+  assert!(backoff.next_delay().unwrap() >= Duration::from_secs(30));
+}
@@ -0,0 +1,366 @@
+//! Process local orders, without RabbitMQ
+
+use crate::{
+  config::get_workspace_keep_on_failure,
+  job::{CancellationToken, Job, JobProgression, JobResult, JobStatus, Workspace},
+  message, MessageError, MessageEvent, Result,
+};
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::{
+  fs::{self, OpenOptions},
+  io::{self, Write},
+  path::{Path, PathBuf},
+  sync::{Arc, RwLock},
+};
+
+lazy_static! {
+  static ref LOCAL_OUTPUT: RwLock<Option<LocalOutput>> = RwLock::new(None);
+}
+
+/// Destination of the results and progressions of the local orders
+#[derive(Clone, Debug, PartialEq)]
+pub enum LocalOutput {
+  Stdout,
+  Directory(PathBuf),
+}
+
+impl LocalOutput {
+  /// Write in the directory, or on the standard output when it is not set or is `-`
+  pub fn new(directory: Option<String>) -> Self {
+    match directory {
+      Some(directory) if !directory.is_empty() && directory != "-" => {
+        LocalOutput::Directory(PathBuf::from(directory))
+      }
+      _ => LocalOutput::Stdout,
+    }
+  }
+
+  /// Write the result of an order, named after the order file when it has no job identifier
+  fn write_result(
+    &self,
+    source_order: &Path,
+    job_id: Option<u64>,
+    content: &Value,
+  ) -> io::Result<()> {
+    match self {
+      LocalOutput::Stdout => writeln!(io::stdout(), "{}", content),
+      LocalOutput::Directory(directory) => {
+        fs::create_dir_all(directory)?;
+        let name = job_id.map(|job_id| job_id.to_string()).unwrap_or_else(|| {
+          source_order
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_else(|| "unknown".to_string())
+        });
+        let path = directory.join(format!("{}_result.json", name));
+        let content = serde_json::to_string_pretty(content)?;
+        fs::write(path, content)
+      }
+    }
+  }
+
+  fn write_progression(&self, job_id: u64, content: &Value) -> io::Result<()> {
+    match self {
+      LocalOutput::Stdout => writeln!(io::stdout(), "{}", content),
+      LocalOutput::Directory(directory) => {
+        fs::create_dir_all(directory)?;
+        let path = get_progressions_path(directory, job_id);
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(file, "{}", content)
+      }
+    }
+  }
+
+  /// Remove the progressions written by a previous run of the job
+  fn reset_progressions(&self, job_id: u64) -> io::Result<()> {
+    match self {
+      LocalOutput::Stdout => Ok(()),
+      LocalOutput::Directory(directory) => {
+        match fs::remove_file(get_progressions_path(directory, job_id)) {
+          Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error),
+          _ => Ok(()),
+        }
+      }
+    }
+  }
+}
+
+fn get_progressions_path(directory: &Path, job_id: u64) -> PathBuf {
+  directory.join(format!("{}_progressions.json", job_id))
+}
+
+/// Write a progression of a local order, if the orders are processed locally
+pub(crate) fn write_progression(job_progression: &JobProgression) {
+  let local_output = LOCAL_OUTPUT.read().ok().and_then(|output| output.clone());
+
+  if let Some(local_output) = local_output {
+    if let Err(error) =
+      local_output.write_progression(job_progression.get_job_id(), &json!(job_progression))
+    {
+      error!("Unable to write progression: {:?}", error);
+    }
+  }
+}
+
+/// List the order files, from paths, directories or glob patterns
+///
+/// A directory or a pattern without any order is an error, to not silently process nothing.
+pub fn expand_source_orders(source_orders: &[String]) -> Result<Vec<PathBuf>> {
+  let mut paths = vec![];
+
+  for source_order in source_orders {
+    let path = Path::new(source_order);
+
+    let mut orders: Vec<PathBuf> = if path.is_dir() {
+      fs::read_dir(path)
+        .map_err(|error| {
+          MessageError::RuntimeError(format!("Unable to list orders in {:?}: {}", path, error))
+        })?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && path.extension() == Some("json".as_ref()))
+        .collect()
+    } else if source_order.contains(&['*', '?', '['][..]) {
+      glob::glob(source_order)
+        .map_err(|error| {
+          MessageError::RuntimeError(format!(
+            "Invalid order pattern {:?}: {}",
+            source_order, error
+          ))
+        })?
+        .filter_map(|entry| entry.ok())
+        .collect()
+    } else {
+      vec![path.to_path_buf()]
+    };
+
+    if orders.is_empty() {
+      return Err(MessageError::RuntimeError(format!(
+        "No order found in {:?}",
+        source_order
+      )));
+    }
+
+    orders.sort();
+    paths.append(&mut orders);
+  }
+
+  Ok(paths)
+}
+
+/// Parse an order and check its parameters, without processing it
+pub fn validate<P: DeserializeOwned + JsonSchema>(source_order: &str) -> Result<()> {
+  let message_data = fs::read_to_string(source_order)
+    .map_err(|error| MessageError::RuntimeError(format!("Unable to read order: {}", error)))?;
+
+  let job = Job::new(&message_data)?;
+  job.get_validated_parameters::<P>()?;
+  Ok(())
+}
+
+/// Process the orders one after the other
+///
+/// Returns `false` if any order failed.
+pub fn process<P, ME>(
+  message_event: Arc<RwLock<ME>>,
+  source_orders: &[String],
+  local_output: LocalOutput,
+) -> bool
+where
+  P: DeserializeOwned + JsonSchema + Send + 'static,
+  ME: MessageEvent<P> + Send + Sync + 'static,
+{
+  if let Ok(mut output) = LOCAL_OUTPUT.write() {
+    *output = Some(local_output.clone());
+  }
+
+  let orders = match expand_source_orders(source_orders) {
+    Ok(orders) => orders,
+    Err(error) => {
+      error!("{:?}", error);
+      return false;
+    }
+  };
+
+  let mut succeeded = true;
+
+  for source_order in orders {
+    info!("Start to process order: {:?}", source_order);
+
+    let message_data = match fs::read_to_string(&source_order) {
+      Ok(message_data) => message_data,
+      Err(error) => {
+        error!("Unable to read order {:?}: {}", source_order, error);
+        succeeded = false;
+        continue;
+      }
+    };
+
+    let job_id = Job::new(&message_data).ok().map(|job| job.job_id);
+    if let Some(job_id) = job_id {
+      if let Err(error) = local_output.reset_progressions(job_id) {
+        error!("Unable to reset progressions of job {}: {}", job_id, error);
+      }
+    }
+
+    let (workspace, result) = match message::create_workspace(job_id) {
+      Ok(workspace) => {
+        let result = message::parse_and_process_message(
+          message_event.clone(),
+          &message_data,
+          None,
+          None,
+          message::publish_job_progression,
+          CancellationToken::default(),
+          workspace.as_ref().map(Workspace::get_path),
+        );
+        (workspace, result)
+      }
+      Err(error) => (None, Err(error)),
+    };
+
+    let job_result = match result {
+      Ok(mut job_result) => {
+        job_result.update_execution_duration();
+        info!(target: &job_result.get_job_id().to_string(), "Process succeeded: {:?}", job_result);
+        Some(job_result)
+      }
+      Err(MessageError::ProcessingError(job_result)) => {
+        error!(target: &job_result.get_job_id().to_string(), "Process failed: {:?}", job_result);
+        Some(job_result.with_status(JobStatus::Error))
+      }
+      Err(error) => {
+        error!("{:?}", error);
+        job_id.map(|job_id| {
+          JobResult::new(job_id)
+            .with_status(JobStatus::Error)
+            .with_message(&format!("{:?}", error))
+        })
+      }
+    };
+
+    let failed = job_result
+      .as_ref()
+      .map(|job_result| job_result.get_status() == &JobStatus::Error)
+      .unwrap_or(true);
+    if failed {
+      succeeded = false;
+    }
+
+    let content = job_result
+      .map(|job_result| json!(job_result))
+      .unwrap_or_else(|| json!({"status": "error", "message": "Invalid order"}));
+
+    if let Err(error) = local_output.write_result(&source_order, job_id, &content) {
+      error!(
+        "Unable to write result of order {:?}: {}",
+        source_order, error
+      );
+    }
+
+    if let Some(workspace) = workspace {
+      workspace.clean(failed, get_workspace_keep_on_failure());
+    }
+  }
+
+  if let Ok(mut output) = LOCAL_OUTPUT.write() {
+    *output = None;
+  }
+
+  succeeded
+}
+
+#[test]
+fn source_orders_expansion() {
+  let orders = expand_source_orders(&["./examples".to_string()]).unwrap();
+  assert!(orders.contains(&PathBuf::from("./examples/success_order.json")));
+  assert!(orders.contains(&PathBuf::from("./examples/error_order.json")));
+  assert!(!orders.contains(&PathBuf::from("./examples/worker.rs")));
+
+  let orders = expand_source_orders(&["examples/*ess_order.json".to_string()]).unwrap();
+  assert_eq!(orders, vec![PathBuf::from("examples/success_order.json")]);
+
+  let orders = expand_source_orders(&["./examples/missing_order.json".to_string()]).unwrap();
+  assert_eq!(orders, vec![PathBuf::from("./examples/missing_order.json")]);
+
+  assert!(expand_source_orders(&["examples/*.missing".to_string()]).is_err());
+  assert!(expand_source_orders(&["./src/parameter/store".to_string()]).is_err());
+}
+
+#[test]
+fn local_progressions_reset() {
+  let directory = std::env::temp_dir().join(format!("mcai_orders_{}", std::process::id()));
+  let local_output = LocalOutput::Directory(directory.clone());
+
+  local_output
+    .write_progression(123, &json!({"progression": 10}))
+    .unwrap();
+  local_output.reset_progressions(123).unwrap();
+  local_output
+    .write_progression(123, &json!({"progression": 20}))
+    .unwrap();
+  local_output.reset_progressions(456).unwrap();
+
+  let content = fs::read_to_string(get_progressions_path(&directory, 123)).unwrap();
+  assert_eq!(content, "{\"progression\":20}\n");
+
+  fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn local_results_names() {
+  let directory = std::env::temp_dir().join(format!("mcai_results_{}", std::process::id()));
+  let local_output = LocalOutput::Directory(directory.clone());
+  let result = |name: &str| fs::read_to_string(directory.join(name)).unwrap();
+
+  let invalid_order = Path::new("orders/invalid_order.json");
+  let other_invalid_order = Path::new("orders/other_invalid_order.json");
+  local_output
+    .write_result(Path::new("orders/order.json"), Some(123), &json!(123))
+    .unwrap();
+  local_output
+    .write_result(invalid_order, None, &json!("invalid"))
+    .unwrap();
+  local_output
+    .write_result(other_invalid_order, None, &json!("other invalid"))
+    .unwrap();
+
+  assert_eq!(result("123_result.json"), "123");
+  assert_eq!(result("invalid_order_result.json"), "\"invalid\"");
+  assert_eq!(
+    result("other_invalid_order_result.json"),
+    "\"other invalid\""
+  );
+
+  fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn local_output_configuration() {
+  assert_eq!(LocalOutput::new(None), LocalOutput::Stdout);
+  assert_eq!(LocalOutput::new(Some("-".to_string())), LocalOutput::Stdout);
+  assert_eq!(
+    LocalOutput::new(Some("/tmp/results".to_string())),
+    LocalOutput::Directory(PathBuf::from("/tmp/results"))
+  );
+}
+
+#[test]
+fn source_order_validation() {
+  #[derive(Debug, Deserialize, JsonSchema)]
+  struct Parameters {
+    #[allow(dead_code)]
+    action: String,
+  }
+
+  #[derive(Debug, Deserialize, JsonSchema)]
+  struct OtherParameters {
+    #[allow(dead_code)]
+    source_path: String,
+  }
+
+  assert!(validate::<Parameters>("./examples/success_order.json").is_ok());
+  assert!(validate::<OtherParameters>("./examples/success_order.json").is_err());
+  assert!(validate::<Parameters>("./examples/missing_order.json").is_err());
+}
@@ -0,0 +1,7 @@
+/// Requests changing the state of the worker consumer
+#[derive(Clone, Debug, PartialEq)]
+pub enum WorkerControl {
+  StopConsuming,
+  ResumeConsuming,
+  Terminate,
+}
@@ -0,0 +1,234 @@
+//! Commands received on the direct messaging queue of a worker instance
+
+use super::{control::WorkerControl, system_information, WorkerConfiguration};
+use crate::{logger::LogLevelHandle, message::InFlightJobs, McaiChannel, MessageError, Result};
+use futures::channel::mpsc::UnboundedSender;
+use lapin::{
+  message::Delivery,
+  options::{BasicAckOptions, BasicPublishOptions},
+  BasicProperties, Promise,
+};
+use log::LevelFilter;
+use serde_json::Value;
+use std::str::FromStr;
+
+static QUEUE_WORKER_STATUS_RESPONSE: &str = "worker_status_response";
+
+/// Command sent to a worker instance
+///
+/// Commands are JSON objects tagged by their `type`, like `{"type": "stop_consuming"}`.
+/// An empty message is handled as a `status` command.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DirectMessage {
+  Status,
+  StopConsuming,
+  ResumeConsuming,
+  StopWorker,
+  GetConfiguration,
+  SetLogLevel { level: String },
+  StopJob { job_id: u64 },
+}
+
+impl DirectMessage {
+  pub fn new(message: &str) -> Result<Self> {
+    if message.trim().is_empty() {
+      return Ok(DirectMessage::Status);
+    }
+
+    serde_json::from_str(message).map_err(|error| {
+      MessageError::RuntimeError(format!("Unable to parse direct message: {}", error))
+    })
+  }
+
+  fn get_type(&self) -> &'static str {
+    match self {
+      DirectMessage::Status => "status",
+      DirectMessage::StopConsuming => "stop_consuming",
+      DirectMessage::ResumeConsuming => "resume_consuming",
+      DirectMessage::StopWorker => "stop_worker",
+      DirectMessage::GetConfiguration => "get_configuration",
+      DirectMessage::SetLogLevel { .. } => "set_log_level",
+      DirectMessage::StopJob { .. } => "stop_job",
+    }
+  }
+}
+
+/// Handle the direct messages of the worker instance, and reply to them
+#[derive(Clone)]
+pub(crate) struct DirectMessageHandler {
+  channel: McaiChannel,
+  worker_configuration: WorkerConfiguration,
+  control_sender: UnboundedSender<WorkerControl>,
+  log_level_handle: LogLevelHandle,
+  in_flight_jobs: InFlightJobs,
+}
+
+impl DirectMessageHandler {
+  pub fn new(
+    channel: McaiChannel,
+    worker_configuration: WorkerConfiguration,
+    control_sender: UnboundedSender<WorkerControl>,
+    log_level_handle: LogLevelHandle,
+    in_flight_jobs: InFlightJobs,
+  ) -> Self {
+    DirectMessageHandler {
+      channel,
+      worker_configuration,
+      control_sender,
+      log_level_handle,
+      in_flight_jobs,
+    }
+  }
+
+  pub fn handle(&self, delivery: Delivery) -> Promise<()> {
+    let message = std::str::from_utf8(&delivery.data)
+      .map_err(|error| MessageError::RuntimeError(format!("{}", error)))
+      .and_then(DirectMessage::new);
+
+    let direct_message = match message {
+      Ok(direct_message) => direct_message,
+      Err(error) => {
+        error!("{:?}", error);
+        let message = match error {
+          MessageError::RuntimeError(message) => message,
+          error => format!("{:?}", error),
+        };
+        self.reply("unknown", Err(message));
+        return self.acknowledge(delivery);
+      }
+    };
+
+    debug!("Received direct message: {:?}", direct_message);
+
+    if direct_message == DirectMessage::Status {
+      return system_information::send_real_time_information(
+        delivery,
+        &self.channel,
+        &self.worker_configuration,
+      );
+    }
+
+    let content = self.execute(&direct_message);
+    self.reply(direct_message.get_type(), content);
+    self.acknowledge(delivery)
+  }
+
+  fn execute(&self, direct_message: &DirectMessage) -> std::result::Result<Value, String> {
+    match direct_message {
+      DirectMessage::Status => Ok(json!({})),
+      DirectMessage::StopConsuming => self.send_control(WorkerControl::StopConsuming),
+      DirectMessage::ResumeConsuming => self.send_control(WorkerControl::ResumeConsuming),
+      DirectMessage::StopWorker => self.send_control(WorkerControl::Terminate),
+      DirectMessage::GetConfiguration => serde_json::to_value(&self.worker_configuration)
+        .map(|configuration| json!({ "configuration": configuration }))
+        .map_err(|error| error.to_string()),
+      DirectMessage::SetLogLevel { level } => {
+        let level_filter =
+          LevelFilter::from_str(level).map_err(|_| format!("Invalid log level: {:?}", level))?;
+        self.log_level_handle.set_level(level_filter);
+        info!("Log level set to {}", level_filter);
+        Ok(json!({ "level": level_filter.to_string().to_lowercase() }))
+      }
+      DirectMessage::StopJob { job_id } => {
+        if !self.in_flight_jobs.cancel(*job_id) {
+          return Err(format!("Job {} is not processed by this worker", job_id));
+        }
+        warn!(target: &job_id.to_string(), "Job cancellation requested");
+        Ok(json!({ "job_id": job_id }))
+      }
+    }
+  }
+
+  fn send_control(&self, control: WorkerControl) -> std::result::Result<Value, String> {
+    self
+      .control_sender
+      .unbounded_send(control)
+      .map(|_| json!({}))
+      .map_err(|error| error.to_string())
+  }
+
+  fn reply(&self, message_type: &str, content: std::result::Result<Value, String>) {
+    let mut response = json!({
+      "instance_id": self.worker_configuration.get_instance_id(),
+      "type": message_type,
+    });
+
+    match content {
+      Ok(Value::Object(fields)) => {
+        response["status"] = json!("ok");
+        for (key, value) in fields {
+          response[key] = value;
+        }
+      }
+      Ok(_) => {
+        response["status"] = json!("ok");
+      }
+      Err(message) => {
+        response["status"] = json!("error");
+        response["message"] = json!(message);
+      }
+    }
+
+    if let Err(error) = self
+      .channel
+      .basic_publish(
+        "",
+        QUEUE_WORKER_STATUS_RESPONSE,
+        BasicPublishOptions::default(),
+        response.to_string().as_bytes().to_vec(),
+        BasicProperties::default(),
+      )
+      .wait()
+    {
+      error!(
+        "Unable to reply to {:?} direct message: {:?}",
+        message_type, error
+      );
+    }
+  }
+
+  /// Acknowledge the command even if the reply failed, as it has already been executed
+  fn acknowledge(&self, delivery: Delivery) -> Promise<()> {
+    self
+      .channel
+      .basic_ack(delivery.delivery_tag, BasicAckOptions::default())
+  }
+}
+
+#[test]
+fn direct_message_parsing() {
+  assert_eq!(DirectMessage::new("").unwrap(), DirectMessage::Status);
+  assert_eq!(
+    DirectMessage::new(r#"{"type": "status"}"#).unwrap(),
+    DirectMessage::Status
+  );
+  assert_eq!(
+    DirectMessage::new(r#"{"type": "stop_consuming"}"#).unwrap(),
+    DirectMessage::StopConsuming
+  );
+  assert_eq!(
+    DirectMessage::new(r#"{"type": "resume_consuming"}"#).unwrap(),
+    DirectMessage::ResumeConsuming
+  );
+  assert_eq!(
+    DirectMessage::new(r#"{"type": "stop_worker"}"#).unwrap(),
+    DirectMessage::StopWorker
+  );
+  assert_eq!(
+    DirectMessage::new(r#"{"type": "get_configuration"}"#).unwrap(),
+    DirectMessage::GetConfiguration
+  );
+  assert_eq!(
+    DirectMessage::new(r#"{"type": "set_log_level", "level": "debug"}"#).unwrap(),
+    DirectMessage::SetLogLevel {
+      level: "debug".to_string()
+    }
+  );
+  assert_eq!(
+    DirectMessage::new(r#"{"type": "stop_job", "job_id": 123}"#).unwrap(),
+    DirectMessage::StopJob { job_id: 123 }
+  );
+  assert!(DirectMessage::new(r#"{"type": "unknown"}"#).is_err());
+  assert!(DirectMessage::new(r#"{"type": "set_log_level"}"#).is_err());
+}
@@ -14,7 +14,10 @@ use crate::{
 use crate::{MessageEvent, Result};
 use serde::de::DeserializeOwned;
 
+pub(crate) mod control;
+pub mod direct_messaging;
 pub mod docker;
+pub(crate) mod shutdown;
 pub mod system_information;
 
 pub mod built_info {
@@ -23,18 +26,38 @@ pub mod built_info {
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum ParameterType {
+  #[serde(rename = "array_of_booleans")]
+  ArrayOfBooleans,
+  #[serde(rename = "array_of_floats")]
+  ArrayOfFloats,
+  #[serde(rename = "array_of_integers")]
+  ArrayOfIntegers,
+  #[serde(rename = "array_of_media_segments")]
+  ArrayOfMediaSegments,
   #[serde(rename = "array_of_strings")]
   ArrayOfStrings,
   #[serde(rename = "boolean")]
   Boolean,
   #[serde(rename = "credential")]
   Credential,
+  #[serde(rename = "datetime")]
+  DateTime,
+  #[serde(rename = "duration")]
+  Duration,
+  #[serde(rename = "float")]
+  Float,
   #[serde(rename = "integer")]
   Integer,
+  #[serde(rename = "json")]
+  Json,
+  #[serde(rename = "object")]
+  Object,
   #[serde(rename = "requirements")]
   Requirements,
   #[serde(rename = "string")]
   String,
+  #[serde(rename = "url")]
+  Url,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -138,6 +161,10 @@ impl WorkerConfiguration {
     "file".to_string()
   }
 
+  pub fn get_parameters_schema(&self) -> &RootSchema {
+    &self.parameters
+  }
+
   pub fn get_direct_messaging_queue_name(&self) -> String {
     format!("direct_messaging_{}", self.instance_id)
   }
@@ -0,0 +1,33 @@
+use super::control::WorkerControl;
+use futures::channel::mpsc::UnboundedSender;
+use signal_hook::{iterator::Signals, SIGINT, SIGTERM};
+use std::thread;
+
+/// Listen SIGTERM and SIGINT signals to request the worker termination
+///
+/// A second signal stops the process immediately.
+pub fn listen_termination_signals(control_sender: UnboundedSender<WorkerControl>) {
+  let signals = match Signals::new([SIGTERM, SIGINT].iter()) {
+    Ok(signals) => signals,
+    Err(error) => {
+      error!("Unable to listen termination signals: {:?}", error);
+      return;
+    }
+  };
+
+  thread::spawn(move || {
+    let mut signals = signals.forever();
+
+    if let Some(signal) = signals.next() {
+      warn!("Received signal {}, stop the worker gracefully", signal);
+      if let Err(error) = control_sender.unbounded_send(WorkerControl::Terminate) {
+        error!("Unable to request the worker termination: {:?}", error);
+      }
+    }
+
+    if let Some(signal) = signals.next() {
+      warn!("Received signal {} again, exit immediately", signal);
+      std::process::exit(1);
+    }
+  });
+}
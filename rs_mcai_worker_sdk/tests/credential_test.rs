@@ -7,6 +7,7 @@ use mcai_worker_sdk::{
 #[test]
 fn test_string_credential_request_value() {
   std::env::set_var("BACKEND_HOSTNAME", mockito::server_url());
+  std::env::set_var("BACKEND_CACHE_TTL", "0");
   use mockito::mock;
 
   let _m = mock("POST", "/sessions")
@@ -47,6 +48,7 @@ fn test_string_credential_request_value() {
 #[test]
 fn test_integer_credential_request_value_from_string() {
   std::env::set_var("BACKEND_HOSTNAME", mockito::server_url());
+  std::env::set_var("BACKEND_CACHE_TTL", "0");
   use mockito::mock;
 
   let _m = mock("POST", "/sessions")
@@ -87,6 +89,7 @@ fn test_integer_credential_request_value_from_string() {
 #[test]
 fn test_integer_credential_request_value_from_integer() {
   std::env::set_var("BACKEND_HOSTNAME", mockito::server_url());
+  std::env::set_var("BACKEND_CACHE_TTL", "0");
   use mockito::mock;
 
   let _m = mock("POST", "/sessions")
@@ -127,6 +130,7 @@ fn test_integer_credential_request_value_from_integer() {
 #[test]
 fn test_boolean_credential_request_value_from_string() {
   std::env::set_var("BACKEND_HOSTNAME", mockito::server_url());
+  std::env::set_var("BACKEND_CACHE_TTL", "0");
   use mockito::mock;
 
   let _m = mock("POST", "/sessions")
@@ -167,6 +171,7 @@ fn test_boolean_credential_request_value_from_string() {
 #[test]
 fn test_boolean_credential_request_value_from_boolean() {
   std::env::set_var("BACKEND_HOSTNAME", mockito::server_url());
+  std::env::set_var("BACKEND_CACHE_TTL", "0");
   use mockito::mock;
 
   let _m = mock("POST", "/sessions")
@@ -207,6 +212,7 @@ fn test_boolean_credential_request_value_from_boolean() {
 #[test]
 fn test_array_credential_request_value_from_array_of_strings() {
   std::env::set_var("BACKEND_HOSTNAME", mockito::server_url());
+  std::env::set_var("BACKEND_CACHE_TTL", "0");
   use mockito::mock;
 
   let _m = mock("POST", "/sessions")
@@ -252,6 +258,7 @@ fn test_array_credential_request_value_from_array_of_strings() {
 #[test]
 fn test_object_credential_request_value_from_media_segments() {
   std::env::set_var("BACKEND_HOSTNAME", mockito::server_url());
+  std::env::set_var("BACKEND_CACHE_TTL", "0");
   use mockito::mock;
 
   let _m = mock("POST", "/sessions")
@@ -297,6 +304,7 @@ fn test_object_credential_request_value_from_media_segments() {
 #[test]
 fn test_string_credential_request_value_no_session() {
   std::env::set_var("BACKEND_HOSTNAME", mockito::server_url());
+  std::env::set_var("BACKEND_CACHE_TTL", "0");
   use mockito::mock;
 
   let _m = mock("POST", "/sessions").with_status(404).create();
@@ -325,6 +333,7 @@ fn test_string_credential_request_value_no_session() {
 #[test]
 fn test_string_credential_request_value_invalid_session() {
   std::env::set_var("BACKEND_HOSTNAME", mockito::server_url());
+  std::env::set_var("BACKEND_CACHE_TTL", "0");
   use mockito::mock;
 
   let _m = mock("POST", "/sessions")
@@ -357,6 +366,7 @@ fn test_string_credential_request_value_invalid_session() {
 #[test]
 fn test_string_credential_request_value_no_credential() {
   std::env::set_var("BACKEND_HOSTNAME", mockito::server_url());
+  std::env::set_var("BACKEND_CACHE_TTL", "0");
   use mockito::mock;
 
   let _m = mock("POST", "/sessions")
@@ -392,6 +402,7 @@ fn test_string_credential_request_value_no_credential() {
 #[test]
 fn test_string_credential_request_value_invalid_credential() {
   std::env::set_var("BACKEND_HOSTNAME", mockito::server_url());
+  std::env::set_var("BACKEND_CACHE_TTL", "0");
   use mockito::mock;
 
   let _m = mock("POST", "/sessions")
@@ -428,6 +439,7 @@ fn test_string_credential_request_value_invalid_credential() {
 #[test]
 fn test_string_credential_request_value_without_store() {
   std::env::set_var("BACKEND_HOSTNAME", mockito::server_url());
+  std::env::set_var("BACKEND_CACHE_TTL", "0");
   use mockito::mock;
 
   let _m = mock("POST", "/sessions")
@@ -509,6 +521,7 @@ fn test_string_credential_request_value_with_other_store() {
 #[test]
 fn test_string_credential_request_value_with_invalid_store() {
   std::env::set_var("BACKEND_HOSTNAME", mockito::server_url());
+  std::env::set_var("BACKEND_CACHE_TTL", "0");
   use mockito::mock;
 
   let _m = mock("POST", "/sessions")
@@ -556,3 +569,109 @@ fn test_string_credential_request_value_with_invalid_store() {
     Err(MessageError::ParameterValueError(error_message))
   );
 }
+
+#[test]
+fn test_credential_request_value_cache() {
+  std::env::set_var("CACHED_BACKEND_HOSTNAME", mockito::server_url());
+  use mockito::mock;
+
+  let session_mock = mock("POST", "/sessions")
+    .with_header("content-type", "application/json")
+    .with_body(r#"{"access_token": "fake_access_token"}"#)
+    .expect(1)
+    .create();
+
+  let first_credential_mock = mock("GET", "/credentials/FIRST_CREDENTIAL_KEY")
+    .with_header("content-type", "application/json")
+    .with_body(
+      r#"{"data": {
+        "id": 666,
+        "key": "FIRST_CREDENTIAL_KEY",
+        "value": "FIRST_CREDENTIAL_VALUE",
+        "inserted_at": "today"
+      }}"#,
+    )
+    .expect(1)
+    .create();
+
+  let second_credential_mock = mock("GET", "/credentials/SECOND_CREDENTIAL_KEY")
+    .with_header("content-type", "application/json")
+    .with_body(
+      r#"{"data": {
+        "id": 667,
+        "key": "SECOND_CREDENTIAL_KEY",
+        "value": "SECOND_CREDENTIAL_VALUE",
+        "inserted_at": "today"
+      }}"#,
+    )
+    .expect(1)
+    .create();
+
+  let message = r#"{
+    "job_id": 123,
+    "parameters": [
+      { "id":"first_credential",
+        "type":"string",
+        "store":"CACHED_BACKEND",
+        "value":"FIRST_CREDENTIAL_KEY"
+      },
+      { "id":"second_credential",
+        "type":"string",
+        "store":"CACHED_BACKEND",
+        "value":"SECOND_CREDENTIAL_KEY"
+      }
+    ]
+  }"#;
+
+  let job = Job::new(message).unwrap();
+
+  for _ in 0..3 {
+    assert_eq!(
+      job.get_parameter::<String>("first_credential"),
+      Ok("FIRST_CREDENTIAL_VALUE".to_string())
+    );
+    assert_eq!(
+      job.get_parameter::<String>("second_credential"),
+      Ok("SECOND_CREDENTIAL_VALUE".to_string())
+    );
+  }
+
+  session_mock.assert();
+  first_credential_mock.assert();
+  second_credential_mock.assert();
+}
+
+#[test]
+fn test_credential_request_value_from_custom_store() {
+  use mcai_worker_sdk::parameter::store::{register_store, CredentialStore};
+  use serde_json::Value;
+  use std::sync::Arc;
+
+  struct CustomStore {}
+
+  impl CredentialStore for CustomStore {
+    fn get_value(&self, credential_key: &str) -> Result<Value, String> {
+      Ok(Value::String(credential_key.to_lowercase()))
+    }
+  }
+
+  register_store("CUSTOM", Arc::new(CustomStore {}));
+
+  let message = r#"{
+    "job_id": 123,
+    "parameters": [
+      { "id":"test_credential",
+        "type":"string",
+        "store":"CUSTOM",
+        "value":"TEST_CREDENTIAL_KEY"
+      }
+    ]
+  }"#;
+
+  let job = Job::new(message).unwrap();
+
+  assert_eq!(
+    job.get_parameter::<String>("test_credential"),
+    Ok("test_credential_key".to_string())
+  );
+}
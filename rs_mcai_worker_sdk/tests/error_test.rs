@@ -1,8 +1,10 @@
 extern crate assert_matches;
 extern crate mcai_worker_sdk;
+#[macro_use]
+extern crate serde_json;
 
 use mcai_worker_sdk::job::{JobResult, JobStatus};
-use mcai_worker_sdk::MessageError;
+use mcai_worker_sdk::{ErrorKind, JobError, MessageError};
 
 #[test]
 pub fn test_message_error_from() {
@@ -17,3 +19,60 @@ pub fn test_message_error_from() {
   );
   assert_eq!(expected, message_error);
 }
+
+#[test]
+pub fn test_message_error_kind() {
+  assert_eq!(
+    MessageError::RuntimeError("error".to_string()).get_kind(),
+    ErrorKind::Permanent
+  );
+  assert_eq!(
+    MessageError::ProcessingError(JobResult::new(123)).get_kind(),
+    ErrorKind::Permanent
+  );
+  assert_eq!(
+    MessageError::ParameterValueError("error".to_string()).get_kind(),
+    ErrorKind::Permanent
+  );
+  assert_eq!(
+    MessageError::NotImplemented().get_kind(),
+    ErrorKind::Permanent
+  );
+  assert_eq!(
+    MessageError::RequirementsError("error".to_string()).get_kind(),
+    ErrorKind::Requirements
+  );
+  assert_eq!(
+    MessageError::JobError(JobError::transient("unavailable service")).get_kind(),
+    ErrorKind::Transient
+  );
+}
+
+#[test]
+pub fn test_job_error_serialization() {
+  let job_error = JobError::permanent("invalid media")
+    .with_code("INVALID_MEDIA")
+    .with_details(&json!({"stream_index": 2}));
+
+  assert_eq!(job_error.get_kind(), ErrorKind::Permanent);
+  assert_eq!(job_error.get_message(), "invalid media");
+  assert_eq!(job_error.get_code(), Some(&"INVALID_MEDIA".to_string()));
+  assert_eq!(
+    serde_json::to_value(&job_error).unwrap(),
+    json!({
+      "kind": "permanent",
+      "message": "invalid media",
+      "code": "INVALID_MEDIA",
+      "details": {"stream_index": 2}
+    })
+  );
+
+  let job_error = JobError::requirements("missing GPU");
+  assert_eq!(
+    serde_json::to_value(&job_error).unwrap(),
+    json!({
+      "kind": "requirements",
+      "message": "missing GPU",
+    })
+  );
+}
@@ -497,3 +497,307 @@ fn test_get_job_parameters_with_unsupported_integer_credential_type() {
   assert!(job_parameters.is_err());
   assert_eq!(expected, job_parameters.unwrap_err());
 }
+
+#[test]
+fn test_get_validated_job_parameters() {
+  #[derive(JsonSchema, Deserialize, Debug, PartialEq)]
+  #[serde(rename_all = "snake_case")]
+  enum Mode {
+    Fast,
+    Accurate,
+  }
+
+  #[derive(JsonSchema, Deserialize, Debug)]
+  struct WorkerJobParameters {
+    source_path: String,
+    mode: Mode,
+    threads: u8,
+    ratio: Option<f64>,
+  }
+
+  let message = r#"{
+    "job_id": 123,
+    "parameters": [
+      { "id":"source_path", "type":"string", "value":"/path/to/file" },
+      { "id":"mode", "type":"string", "value":"fast" },
+      { "id":"threads", "type":"integer", "value":4 }
+    ]
+  }"#;
+
+  let job = Job::new(message).unwrap();
+  let parameters = job
+    .get_validated_parameters::<WorkerJobParameters>()
+    .unwrap();
+  assert_eq!(parameters.source_path, "/path/to/file");
+  assert_eq!(parameters.mode, Mode::Fast);
+  assert_eq!(parameters.threads, 4);
+  assert_eq!(parameters.ratio, None);
+
+  let message = r#"{
+    "job_id": 123,
+    "parameters": [
+      { "id":"mode", "type":"string", "value":"slow" },
+      { "id":"threads", "type":"integer", "value":-1 },
+      { "id":"ratio", "type":"string", "value":"half" }
+    ]
+  }"#;
+
+  let job = Job::new(message).unwrap();
+  let error = job
+    .get_validated_parameters::<WorkerJobParameters>()
+    .unwrap_err();
+
+  assert_eq!(
+    error,
+    MessageError::ParameterValueError(
+      "Invalid parameters: \
+       /source_path: missing required parameter; \
+       /mode: \"slow\" is not one of \"fast\", \"accurate\"; \
+       /ratio: expected number or null, found string; \
+       /threads: -1 is lower than the minimum 0"
+        .to_string()
+    )
+  );
+  assert_eq!(error.get_kind(), mcai_worker_sdk::ErrorKind::Permanent);
+
+  let violations = job.validate_parameters::<WorkerJobParameters>().unwrap();
+  let paths: Vec<&str> = violations
+    .iter()
+    .map(|violation| violation.path.as_str())
+    .collect();
+  assert_eq!(paths, vec!["/source_path", "/mode", "/ratio", "/threads"]);
+  assert_eq!(violations[3].message, "-1 is lower than the minimum 0");
+}
+
+#[test]
+fn test_get_validated_job_parameters_integer_range() {
+  #[derive(JsonSchema, Deserialize, Debug)]
+  struct WorkerJobParameters {
+    #[allow(dead_code)]
+    threads: u8,
+    #[allow(dead_code)]
+    offset: i16,
+  }
+
+  let message = r#"{
+    "job_id": 123,
+    "parameters": [
+      { "id":"threads", "type":"integer", "value":300 },
+      { "id":"offset", "type":"integer", "value":70000 }
+    ]
+  }"#;
+
+  let job = Job::new(message).unwrap();
+  assert_eq!(
+    job
+      .get_validated_parameters::<WorkerJobParameters>()
+      .unwrap_err(),
+    MessageError::ParameterValueError(
+      "Invalid parameters: \
+       /offset: 70000 is greater than the maximum 32767; \
+       /threads: 300 is greater than the maximum 255"
+        .to_string()
+    )
+  );
+
+  let message = r#"{
+    "job_id": 123,
+    "parameters": [
+      { "id":"threads", "type":"integer", "value":255 },
+      { "id":"offset", "type":"integer", "value":-32768 }
+    ]
+  }"#;
+
+  let job = Job::new(message).unwrap();
+  assert!(job
+    .get_validated_parameters::<WorkerJobParameters>()
+    .is_ok());
+}
+
+#[test]
+fn test_job_secret_parameters_redaction() {
+  std::env::set_var("TEST_REDACTED_PASSWORD", "s3cr3t_value");
+
+  let message = r#"{
+    "job_id": 123,
+    "parameters": [
+      { "id":"password", "type":"string", "store":"env", "value":"TEST_REDACTED_PASSWORD" },
+      { "id":"token", "type":"credential", "value":"t0k3n_value" },
+      { "id":"source_path", "type":"string", "value":"/path/to/file" }
+    ]
+  }"#;
+
+  let job = Job::new(message).unwrap();
+
+  #[derive(JsonSchema, Deserialize, Debug)]
+  struct WorkerJobParameters {
+    #[allow(dead_code)]
+    password: i64,
+  }
+
+  let error = job.get_parameters::<WorkerJobParameters>().unwrap_err();
+  let message = format!("{:?}", error);
+  assert!(message.contains("[REDACTED]"));
+  assert!(message.contains("/path/to/file"));
+
+  let debug = format!("{:?}", job);
+  assert!(!debug.contains("t0k3n_value"));
+  assert!(debug.contains("/path/to/file"));
+
+  for error in &[
+    job.get_parameters::<WorkerJobParameters>().unwrap_err(),
+    job
+      .get_validated_parameters::<WorkerJobParameters>()
+      .unwrap_err(),
+  ] {
+    let message = format!("{:?}", error);
+    assert!(!message.contains("s3cr3t_value"));
+    assert!(!message.contains("t0k3n_value"));
+  }
+
+  let message = r#"{
+    "job_id": 123,
+    "parameters": [
+      { "id":"pin", "type":"credential", "value":987654 }
+    ]
+  }"#;
+
+  #[derive(JsonSchema, Deserialize, Debug)]
+  struct PinJobParameters {
+    #[allow(dead_code)]
+    pin: String,
+  }
+
+  let pin_job = Job::new(message).unwrap();
+  for error in &[
+    pin_job.get_parameters::<PinJobParameters>().unwrap_err(),
+    pin_job
+      .get_validated_parameters::<PinJobParameters>()
+      .unwrap_err(),
+  ] {
+    let message = format!("{:?}", error);
+    assert!(!message.contains("987654"));
+  }
+
+  let job_result = JobResult::new(123).with_parameters(&mut job.parameters.clone());
+  assert!(!format!("{:?}", job_result).contains("t0k3n_value"));
+  let serialized = serde_json::to_string(&job_result).unwrap();
+  assert!(!serialized.contains("t0k3n_value"));
+  assert!(serialized.contains("/path/to/file"));
+
+  std::env::remove_var("TEST_REDACTED_PASSWORD");
+}
+
+#[test]
+fn test_get_templated_job_parameters() {
+  let message = r#"{
+    "job_id": 123,
+    "parameters": [
+      { "id":"work_dir", "type":"string", "value":"/data" },
+      { "id":"destination_path", "type":"string", "value":"{work_dir}/{job_id}/out.mp4", "template": true }
+    ]
+  }"#;
+
+  #[derive(JsonSchema, Deserialize, Debug)]
+  struct WorkerJobParameters {
+    destination_path: String,
+  }
+
+  let job = Job::new(message).unwrap();
+  let parameters = job
+    .get_validated_parameters::<WorkerJobParameters>()
+    .unwrap();
+  assert_eq!(parameters.destination_path, "/data/123/out.mp4");
+  assert_eq!(
+    job.get_templated_parameter::<String>("destination_path"),
+    Ok("/data/123/out.mp4".to_string())
+  );
+  assert_eq!(
+    job.get_templated_parameter::<String>("work_dir"),
+    Ok("/data".to_string())
+  );
+
+  let message = r#"{
+    "job_id": 123,
+    "parameters": [
+      { "id":"destination_path", "type":"string", "value":"{work_dir}/{job_id}/out.mp4", "template": true }
+    ]
+  }"#;
+
+  let job = Job::new(message).unwrap();
+  let error = MessageError::ParameterValueError(
+    "Undefined parameter 'work_dir' referenced by parameter 'destination_path'".to_string(),
+  );
+  assert_eq!(
+    job
+      .get_validated_parameters::<WorkerJobParameters>()
+      .unwrap_err(),
+    error
+  );
+  assert_eq!(
+    job.get_templated_parameter::<String>("destination_path"),
+    Err(error)
+  );
+
+  let message = r#"{
+    "job_id": 123,
+    "parameters": [
+      { "id":"destination_path", "type":"string", "value":"{work_dir}/{job_id}/out.mp4" }
+    ]
+  }"#;
+
+  // the parameters are not templated by default
+  let job = Job::new(message).unwrap();
+  let parameters = job.get_parameters::<WorkerJobParameters>().unwrap();
+  assert_eq!(parameters.destination_path, "{work_dir}/{job_id}/out.mp4");
+}
+
+#[test]
+fn test_get_job_timeout() {
+  let message = r#"{
+    "job_id": 123,
+    "parameters": [
+      { "id": "sdk_timeout_ms", "type": "integer", "value": 1500 }
+    ]
+  }"#;
+
+  let job = Job::new(message).unwrap();
+  assert_eq!(
+    job.get_timeout(),
+    Some(std::time::Duration::from_millis(1500))
+  );
+
+  let message = r#"{
+    "job_id": 123,
+    "parameters": [
+      { "id": "sdk_timeout_ms", "type": "integer", "value": 0 }
+    ]
+  }"#;
+
+  let job = Job::new(message).unwrap();
+  assert_eq!(job.get_timeout(), None);
+}
+
+#[test]
+fn test_job_workspace_parameter() {
+  #[derive(Debug, Deserialize, JsonSchema)]
+  struct WorkerParameters {
+    sdk_workspace: String,
+    output: String,
+  }
+
+  let message = r#"{
+    "job_id": 123,
+    "parameters": [
+      { "id": "output", "type": "string", "value": "{sdk_workspace}/out.mp4", "template": true },
+      { "id": "sdk_workspace", "type": "string", "value": "/overridden" }
+    ]
+  }"#;
+
+  let mut job = Job::new(message).unwrap();
+  job.set_workspace(std::path::Path::new("/tmp/job_123_0000abcd"));
+
+  let parameters: WorkerParameters = job.get_validated_parameters().unwrap();
+  assert_eq!(parameters.sdk_workspace, "/tmp/job_123_0000abcd");
+  assert_eq!(parameters.output, "/tmp/job_123_0000abcd/out.mp4");
+}
@@ -1,7 +1,12 @@
 extern crate mcai_worker_sdk;
+#[macro_use]
+extern crate serde_json;
 
+use chrono::{DateTime, Utc};
 use mcai_worker_sdk::{parameter::MediaSegments, MessageError, ParameterValue, Requirement};
 use serde_json::{Number, Value};
+use std::{collections::HashMap, time::Duration};
+use url::Url;
 
 #[test]
 fn test_parameter_value_types_as_string() {
@@ -21,6 +26,33 @@ fn test_parameter_value_types_as_string() {
     "array_of_media_segments".to_string(),
     MediaSegments::get_type_as_string()
   );
+  assert_eq!("integer".to_string(), u8::get_type_as_string());
+  assert_eq!("integer".to_string(), i32::get_type_as_string());
+  assert_eq!("integer".to_string(), u64::get_type_as_string());
+  assert_eq!("float".to_string(), f32::get_type_as_string());
+  assert_eq!(
+    "array_of_integers".to_string(),
+    Vec::<i64>::get_type_as_string()
+  );
+  assert_eq!(
+    "array_of_floats".to_string(),
+    Vec::<f64>::get_type_as_string()
+  );
+  assert_eq!(
+    "array_of_booleans".to_string(),
+    Vec::<bool>::get_type_as_string()
+  );
+  assert_eq!(
+    "object".to_string(),
+    HashMap::<String, Value>::get_type_as_string()
+  );
+  assert_eq!("json".to_string(), Value::get_type_as_string());
+  assert_eq!(
+    "datetime".to_string(),
+    DateTime::<Utc>::get_type_as_string()
+  );
+  assert_eq!("duration".to_string(), Duration::get_type_as_string());
+  assert_eq!("url".to_string(), Url::get_type_as_string());
 }
 
 #[test]
@@ -216,3 +248,99 @@ fn test_parameter_value_bool() {
     result.unwrap_err()
   );
 }
+
+#[test]
+fn test_parameter_value_unsigned_and_smaller_integers() {
+  assert_eq!(Ok(255), u8::from_value(json!(255)));
+  assert_eq!(Ok(42), u16::from_value(json!("42")));
+  assert_eq!(Ok(-12), i32::from_value(json!(-12)));
+  assert_eq!(Ok(u64::MAX), u64::from_value(json!(u64::MAX)));
+  assert_eq!(Ok(1.5), f32::from_value(json!(1.5)));
+
+  assert_eq!(
+    MessageError::ParameterValueError(
+      "Cannot convert value type 'Number(256)' to type u8".to_string()
+    ),
+    u8::from_value(json!(256)).unwrap_err()
+  );
+  assert_eq!(
+    MessageError::ParameterValueError(
+      "Cannot convert value type 'Number(-1)' to type u32".to_string()
+    ),
+    u32::from_value(json!(-1)).unwrap_err()
+  );
+  assert!(u8::from_value(Value::Null).is_err());
+}
+
+#[test]
+fn test_parameter_value_arrays_and_objects() {
+  assert_eq!(
+    Ok(vec![1, -2, 3]),
+    Vec::<i64>::from_value(json!([1, -2, 3]))
+  );
+  assert_eq!(Ok(vec![0.5, 2.0]), Vec::<f64>::from_value(json!([0.5, 2])));
+  assert_eq!(
+    Ok(vec![true, false]),
+    Vec::<bool>::from_value(json!([true, false]))
+  );
+  assert!(Vec::<i64>::from_value(json!(["a"])).is_err());
+
+  let mut object = HashMap::new();
+  object.insert("key".to_string(), json!({"nested": [1, 2]}));
+  assert_eq!(
+    Ok(object),
+    HashMap::<String, Value>::from_value(json!({"key": {"nested": [1, 2]}}))
+  );
+  assert!(HashMap::<String, Value>::from_value(json!([1, 2])).is_err());
+
+  assert_eq!(Ok(json!([1, "two"])), Value::from_value(json!([1, "two"])));
+}
+
+#[test]
+fn test_parameter_value_datetime_duration_url() {
+  assert_eq!(
+    Ok(
+      "2020-10-17T09:30:00+00:00"
+        .parse::<DateTime<Utc>>()
+        .unwrap()
+    ),
+    DateTime::<Utc>::from_value(json!("2020-10-17T09:30:00Z"))
+  );
+  assert!(DateTime::<Utc>::from_value(json!("yesterday")).is_err());
+
+  assert_eq!(Ok(Duration::from_secs(90)), Duration::from_value(json!(90)));
+  assert_eq!(
+    Ok(Duration::from_millis(1500)),
+    Duration::from_value(json!(1.5))
+  );
+  assert_eq!(
+    Ok(Duration::from_millis(500)),
+    Duration::from_value(json!("500ms"))
+  );
+  assert_eq!(
+    Ok(Duration::from_secs(30)),
+    Duration::from_value(json!("30s"))
+  );
+  assert_eq!(
+    Ok(Duration::from_secs(300)),
+    Duration::from_value(json!("5m"))
+  );
+  assert_eq!(
+    Ok(Duration::from_secs(7200)),
+    Duration::from_value(json!("2h"))
+  );
+  assert_eq!(
+    Ok(Duration::from_secs(2)),
+    Duration::from_value(json!({"secs": 2, "nanos": 0}))
+  );
+  assert!(Duration::from_value(json!("2 days")).is_err());
+  assert!(Duration::from_value(json!(-1)).is_err());
+  assert!(Duration::from_value(json!(1e20)).is_err());
+  assert!(Duration::from_value(json!("10000000000000000000h")).is_err());
+
+  assert_eq!(
+    Ok(Url::parse("https://media-cloud.ai/path?query=1").unwrap()),
+    Url::from_value(json!("https://media-cloud.ai/path?query=1"))
+  );
+  assert!(Url::from_value(json!("not an url")).is_err());
+}